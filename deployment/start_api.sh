@@ -6,4 +6,4 @@
 # POSTGRES_USER
 # POSTGRES_DB
 
-/usr/local/bin/uvicorn --app-dir /usr/local/bin/ broker-api:app --host 0.0.0.0 --port 18888
+RUST_LOG=info /usr/local/bin/bgpkit-broker-api --host 0.0.0.0 --port 18888
@@ -13,5 +13,4 @@ else
   KAFKA_OPTS=""
 fi
 
-RUST_LOG=bgpkit_broker_backend /usr/local/bin/bgpkit-broker-updater -c /usr/local/etc/bgpkit-broker-collectors.conf --mode latest  ${KAFKA_OPTS} # 2>/tmp/bgpkit-broker-updater.log
-PGPASSWORD=$POSTGRES_PASSWORD psql -h $POSTGRES_HOST -U $POSTGRES_USER $POSTGRES_DB -c "REFRESH MATERIALIZED VIEW latest_times"
\ No newline at end of file
+RUST_LOG=bgpkit_broker_backend /usr/local/bin/bgpkit-broker-updater -c /usr/local/etc/bgpkit-broker-collectors.conf --mode latest  ${KAFKA_OPTS} # 2>/tmp/bgpkit-broker-updater.log
\ No newline at end of file
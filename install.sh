@@ -4,4 +4,5 @@ set -e
 cargo build --release
 
 sudo cp ./target/release/bgpkit-broker-updater /usr/local/bin/bgpkit-broker-updater
+sudo cp ./target/release/bgpkit-broker-api /usr/local/bin/bgpkit-broker-api
 sudo cp ./deployment/full-config.json /usr/local/etc/bgpkit-broker-collectors.json
\ No newline at end of file
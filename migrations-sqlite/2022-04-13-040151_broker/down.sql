@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP VIEW IF EXISTS latest_times;
+DROP TABLE IF EXISTS items;
+DROP TABLE IF EXISTS collectors;
@@ -0,0 +1,5 @@
+ALTER TABLE items ADD COLUMN missing boolean NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS unverified
+    ON items (collector_id, ts_start)
+    WHERE exact_size = 0 AND NOT missing;
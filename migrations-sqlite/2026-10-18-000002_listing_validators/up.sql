@@ -0,0 +1,8 @@
+CREATE TABLE IF NOT EXISTS listing_validators
+(
+    url text NOT NULL,
+    etag text,
+    last_modified text,
+    updated_at timestamp NOT NULL,
+    CONSTRAINT listing_validators_pkey PRIMARY KEY (url)
+);
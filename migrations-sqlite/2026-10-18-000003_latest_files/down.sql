@@ -0,0 +1,22 @@
+-- This file should undo anything in `up.sql`
+
+DROP VIEW IF EXISTS latest_times;
+DROP TABLE IF EXISTS latest_files;
+
+CREATE VIEW IF NOT EXISTS latest_times
+AS
+SELECT items.ts_start AS "timestamp",
+       items.collector_id,
+       items.data_type,
+       items.rough_size,
+       items.exact_size,
+       collectors.project,
+       collectors.url AS collector_url,
+       items.url AS item_url
+FROM ( SELECT max(items_1.ts_start) AS ts_start,
+              items_1.collector_id,
+              items_1.data_type
+       FROM items items_1
+       GROUP BY items_1.collector_id, items_1.data_type) nested
+         JOIN collectors ON nested.collector_id = collectors.id
+         JOIN items ON nested.ts_start = items.ts_start AND nested.collector_id = items.collector_id AND items.data_type = nested.data_type;
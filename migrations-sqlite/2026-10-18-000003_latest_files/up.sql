@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS latest_files
+(
+    collector_id text NOT NULL,
+    data_type text NOT NULL,
+    ts_start timestamp NOT NULL,
+    item_url text NOT NULL,
+    CONSTRAINT latest_files_pkey PRIMARY KEY (collector_id, data_type),
+    CONSTRAINT latest_files_collector_id_fkey FOREIGN KEY (collector_id)
+        REFERENCES collectors (id)
+);
+
+-- with max(), SQLite takes the bare columns from the row holding the maximum
+INSERT OR IGNORE INTO latest_files(collector_id, data_type, ts_start, item_url)
+SELECT collector_id, data_type, max(ts_start), url
+FROM items
+GROUP BY collector_id, data_type;
+
+DROP VIEW IF EXISTS latest_times;
+
+CREATE VIEW latest_times
+AS
+SELECT latest_files.ts_start AS "timestamp",
+       latest_files.collector_id,
+       latest_files.data_type,
+       items.rough_size,
+       items.exact_size,
+       collectors.project,
+       collectors.url AS collector_url,
+       latest_files.item_url
+FROM latest_files
+         JOIN collectors ON latest_files.collector_id = collectors.id
+         JOIN items ON latest_files.item_url = items.url;
@@ -0,0 +1,29 @@
+-- This file should undo anything in `up.sql`
+
+DROP VIEW IF EXISTS latest_times;
+
+ALTER TABLE collectors DROP COLUMN country;
+ALTER TABLE collectors DROP COLUMN location;
+ALTER TABLE collectors DROP COLUMN latitude;
+ALTER TABLE collectors DROP COLUMN longitude;
+ALTER TABLE collectors DROP COLUMN org;
+ALTER TABLE collectors DROP COLUMN ip_family;
+ALTER TABLE collectors DROP COLUMN activated_on;
+ALTER TABLE collectors DROP COLUMN decommissioned_on;
+ALTER TABLE collectors DROP COLUMN rib_interval_minutes;
+ALTER TABLE collectors DROP COLUMN update_interval_minutes;
+ALTER TABLE collectors DROP COLUMN active;
+
+CREATE VIEW latest_times
+AS
+SELECT latest_files.ts_start AS "timestamp",
+       latest_files.collector_id,
+       latest_files.data_type,
+       items.rough_size,
+       items.exact_size,
+       collectors.project,
+       collectors.url AS collector_url,
+       latest_files.item_url
+FROM latest_files
+         JOIN collectors ON latest_files.collector_id = collectors.id
+         JOIN items ON latest_files.item_url = items.url;
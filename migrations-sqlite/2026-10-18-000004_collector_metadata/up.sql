@@ -0,0 +1,28 @@
+ALTER TABLE collectors ADD COLUMN country text;
+ALTER TABLE collectors ADD COLUMN location text;
+ALTER TABLE collectors ADD COLUMN latitude double precision;
+ALTER TABLE collectors ADD COLUMN longitude double precision;
+ALTER TABLE collectors ADD COLUMN org text;
+ALTER TABLE collectors ADD COLUMN ip_family text;
+ALTER TABLE collectors ADD COLUMN activated_on date;
+ALTER TABLE collectors ADD COLUMN decommissioned_on date;
+ALTER TABLE collectors ADD COLUMN rib_interval_minutes bigint;
+ALTER TABLE collectors ADD COLUMN update_interval_minutes bigint;
+ALTER TABLE collectors ADD COLUMN active boolean NOT NULL DEFAULT true;
+
+DROP VIEW IF EXISTS latest_times;
+
+CREATE VIEW latest_times
+AS
+SELECT latest_files.ts_start AS "timestamp",
+       latest_files.collector_id,
+       latest_files.data_type,
+       items.rough_size,
+       items.exact_size,
+       collectors.project,
+       collectors.url AS collector_url,
+       latest_files.item_url,
+       collectors.active
+FROM latest_files
+         JOIN collectors ON latest_files.collector_id = collectors.id
+         JOIN items ON latest_files.item_url = items.url;
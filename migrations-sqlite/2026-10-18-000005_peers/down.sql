@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS unindexed_ribs;
+ALTER TABLE items DROP COLUMN peers_indexed;
+DROP TABLE IF EXISTS peers;
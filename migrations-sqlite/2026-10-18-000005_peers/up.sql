@@ -0,0 +1,22 @@
+CREATE TABLE IF NOT EXISTS peers
+(
+    collector_id text NOT NULL,
+    peer_asn bigint NOT NULL,
+    peer_ip text NOT NULL,
+    peer_bgp_id text NOT NULL,
+    first_seen timestamp NOT NULL,
+    last_seen timestamp NOT NULL,
+    CONSTRAINT peers_pkey PRIMARY KEY (collector_id, peer_ip, peer_asn),
+    CONSTRAINT peers_collector_id_fkey FOREIGN KEY (collector_id)
+        REFERENCES collectors (id)
+);
+
+CREATE INDEX IF NOT EXISTS peers_asn ON peers (peer_asn);
+
+CREATE INDEX IF NOT EXISTS peers_ip ON peers (peer_ip);
+
+ALTER TABLE items ADD COLUMN peers_indexed boolean NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS unindexed_ribs
+    ON items (collector_id, ts_start)
+    WHERE data_type = 'rib' AND NOT peers_indexed AND NOT missing;
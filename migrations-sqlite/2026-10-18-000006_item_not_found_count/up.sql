@@ -0,0 +1 @@
+ALTER TABLE items ADD COLUMN not_found_count integer NOT NULL DEFAULT 0;
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+
+DROP INDEX IF EXISTS unverified;
+ALTER TABLE items DROP COLUMN IF EXISTS missing;
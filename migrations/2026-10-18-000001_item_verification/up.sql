@@ -0,0 +1,7 @@
+ALTER TABLE items ADD COLUMN IF NOT EXISTS missing boolean NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS unverified
+    ON items USING btree
+        (collector_id, ts_start)
+    TABLESPACE pg_default
+    WHERE exact_size = 0 AND NOT missing;
@@ -0,0 +1,31 @@
+-- This file should undo anything in `up.sql`
+
+DROP VIEW IF EXISTS latest_times;
+
+ALTER TABLE collectors
+    DROP COLUMN IF EXISTS country,
+    DROP COLUMN IF EXISTS location,
+    DROP COLUMN IF EXISTS latitude,
+    DROP COLUMN IF EXISTS longitude,
+    DROP COLUMN IF EXISTS org,
+    DROP COLUMN IF EXISTS ip_family,
+    DROP COLUMN IF EXISTS activated_on,
+    DROP COLUMN IF EXISTS decommissioned_on,
+    DROP COLUMN IF EXISTS rib_interval_minutes,
+    DROP COLUMN IF EXISTS update_interval_minutes,
+    DROP COLUMN IF EXISTS active;
+
+CREATE VIEW latest_times
+AS
+SELECT latest_files.ts_start AS "timestamp",
+       (now() AT TIME ZONE 'utc'::text) - latest_files.ts_start AS delay,
+       latest_files.collector_id,
+       latest_files.data_type,
+       items.rough_size,
+       items.exact_size,
+       collectors.project,
+       collectors.url AS collector_url,
+       latest_files.item_url
+FROM latest_files
+         JOIN collectors ON latest_files.collector_id = collectors.id
+         JOIN items ON latest_files.item_url = items.url;
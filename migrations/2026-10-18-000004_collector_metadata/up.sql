@@ -0,0 +1,28 @@
+ALTER TABLE collectors
+    ADD COLUMN IF NOT EXISTS country text,
+    ADD COLUMN IF NOT EXISTS location text,
+    ADD COLUMN IF NOT EXISTS latitude double precision,
+    ADD COLUMN IF NOT EXISTS longitude double precision,
+    ADD COLUMN IF NOT EXISTS org text,
+    ADD COLUMN IF NOT EXISTS ip_family text,
+    ADD COLUMN IF NOT EXISTS activated_on date,
+    ADD COLUMN IF NOT EXISTS decommissioned_on date,
+    ADD COLUMN IF NOT EXISTS rib_interval_minutes bigint,
+    ADD COLUMN IF NOT EXISTS update_interval_minutes bigint,
+    ADD COLUMN IF NOT EXISTS active boolean NOT NULL DEFAULT true;
+
+CREATE OR REPLACE VIEW latest_times
+AS
+SELECT latest_files.ts_start AS "timestamp",
+       (now() AT TIME ZONE 'utc'::text) - latest_files.ts_start AS delay,
+       latest_files.collector_id,
+       latest_files.data_type,
+       items.rough_size,
+       items.exact_size,
+       collectors.project,
+       collectors.url AS collector_url,
+       latest_files.item_url,
+       collectors.active
+FROM latest_files
+         JOIN collectors ON latest_files.collector_id = collectors.id
+         JOIN items ON latest_files.item_url = items.url;
@@ -0,0 +1,32 @@
+CREATE TABLE IF NOT EXISTS peers
+(
+    collector_id text NOT NULL,
+    peer_asn bigint NOT NULL,
+    peer_ip text NOT NULL,
+    peer_bgp_id text NOT NULL,
+    first_seen timestamp NOT NULL,
+    last_seen timestamp NOT NULL,
+    CONSTRAINT peers_pkey PRIMARY KEY (collector_id, peer_ip, peer_asn),
+    CONSTRAINT peers_collector_id_fkey FOREIGN KEY (collector_id)
+        REFERENCES collectors (id) MATCH SIMPLE
+        ON UPDATE NO ACTION
+        ON DELETE NO ACTION
+);
+
+CREATE INDEX IF NOT EXISTS peers_asn
+    ON peers USING btree
+        (peer_asn)
+    TABLESPACE pg_default;
+
+CREATE INDEX IF NOT EXISTS peers_ip
+    ON peers USING btree
+        (peer_ip)
+    TABLESPACE pg_default;
+
+ALTER TABLE items ADD COLUMN IF NOT EXISTS peers_indexed boolean NOT NULL DEFAULT false;
+
+CREATE INDEX IF NOT EXISTS unindexed_ribs
+    ON items USING btree
+        (collector_id, ts_start)
+    TABLESPACE pg_default
+    WHERE data_type = 'rib' AND NOT peers_indexed AND NOT missing;
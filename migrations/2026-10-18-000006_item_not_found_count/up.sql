@@ -0,0 +1 @@
+ALTER TABLE items ADD COLUMN IF NOT EXISTS not_found_count integer NOT NULL DEFAULT 0;
@@ -0,0 +1,339 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use axum::extract::{Query, State};
+use axum::http::StatusCode;
+use axum::{Json, Router};
+use axum::routing::get;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use clap::Parser;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use tower_http::cors::{Any, CorsLayer};
+use bgpkit_broker_backend::db::{DbConnection, get_db_url};
+use bgpkit_broker_backend::db::models::{Collector, DataType, Item, ItemFilter, Latest, Peer, PeerFilter, Project};
+
+/// Maximum number of items returned by one search request.
+const MAX_PAGE_SIZE: i64 = 10_000;
+
+#[derive(Parser)]
+struct Opts {
+    /// Database URL string, this overwrites the DATABASE_URL env variable
+    #[clap(short, long)]
+    db_url: Option<String>,
+
+    /// Host address to listen on
+    #[clap(long, default_value = "0.0.0.0")]
+    host: String,
+
+    /// Port to listen on
+    #[clap(short, long, default_value = "18888")]
+    port: u16,
+
+    /// Maximum number of database connections
+    #[clap(long, default_value = "10")]
+    max_connections: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchQuery {
+    /// start timestamp, in unix time or RFC3339 format
+    ts_start: Option<String>,
+    /// end timestamp, in unix time or RFC3339 format
+    ts_end: Option<String>,
+    /// filter by project name, i.e. route-views, riperis, pch or isolario
+    project: Option<String>,
+    /// filter by collector names, comma-separated, e.g. rrc00,route-views2
+    collector_id: Option<String>,
+    /// rib or update
+    data_type: Option<String>,
+    /// only files from while their collector peered with this AS, i.e. from the first indexed
+    /// RIB dump listing the peer up to the next indexed dump not listing it
+    peer_asn: Option<u32>,
+    /// only files from while their collector peered with this IP address, i.e. from the first
+    /// indexed RIB dump listing the peer up to the next indexed dump not listing it
+    peer_ip: Option<String>,
+    /// the page number starting from 1, default is 1
+    page: Option<i64>,
+    /// the size of each page, default is 100, at most 10000
+    page_size: Option<i64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct SearchResult {
+    count: Option<usize>,
+    page: Option<i64>,
+    page_size: Option<i64>,
+    error: Option<String>,
+    data: Option<Vec<Item>>,
+}
+
+impl SearchResult {
+    fn error(msg: String) -> (StatusCode, Json<SearchResult>) {
+        (StatusCode::BAD_REQUEST, Json(SearchResult{ error: Some(msg), ..Default::default() }))
+    }
+
+    fn internal_error(msg: String) -> (StatusCode, Json<SearchResult>) {
+        error!("{}", msg);
+        (StatusCode::INTERNAL_SERVER_ERROR, Json(SearchResult{ error: Some(msg), ..Default::default() }))
+    }
+}
+
+/// Parse a timestamp string in unix time, RFC3339, or `YYYY-MM-DD[THH:MM:SS]` format, in UTC.
+fn parse_time_str(ts_str: &str) -> Option<NaiveDateTime> {
+    let ts_str = ts_str.trim();
+    if !ts_str.is_empty() && ts_str.chars().all(|c| c.is_ascii_digit()) {
+        return DateTime::from_timestamp(ts_str.parse::<i64>().ok()?, 0).map(|t| t.naive_utc())
+    }
+    if let Ok(ts) = DateTime::parse_from_rfc3339(ts_str) {
+        return Some(ts.naive_utc())
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(ts) = NaiveDateTime::parse_from_str(ts_str, format) {
+            return Some(ts)
+        }
+    }
+    NaiveDate::parse_from_str(ts_str, "%Y-%m-%d").ok().and_then(|d| d.and_hms_opt(0, 0, 0))
+}
+
+/// Map project name aliases to projects.
+fn parse_project(project: &str) -> Option<Project> {
+    match project.to_lowercase().as_str() {
+        "route-views" | "routeviews" | "rv" => Some(Project::RouteViews),
+        "ripe-ris" | "riperis" | "ris" => Some(Project::RipeRis),
+        "pch" | "packet-clearing-house" => Some(Project::Pch),
+        "isolario" => Some(Project::Isolario),
+        _ => None
+    }
+}
+
+/// Check that the page and page size are positive, the page size is at most [MAX_PAGE_SIZE],
+/// and the page's offset does not overflow.
+fn check_paging(page: i64, page_size: i64) -> Result<(), String> {
+    if page < 1 {
+        return Err(format!("page must be greater than 0, got {}", page))
+    }
+    if !(1..=MAX_PAGE_SIZE).contains(&page_size) {
+        return Err(format!("page_size must be between 1 and {}, got {}", MAX_PAGE_SIZE, page_size))
+    }
+    match (page - 1).checked_mul(page_size) {
+        Some(_) => Ok(()),
+        None => Err(format!("page {} is out of range for page_size {}", page, page_size))
+    }
+}
+
+async fn search(State(db): State<Arc<DbConnection>>, Query(query): Query<SearchQuery>) -> (StatusCode, Json<SearchResult>) {
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(100);
+    if let Err(e) = check_paging(page, page_size) {
+        return SearchResult::error(e)
+    }
+
+    let mut filter = ItemFilter::default();
+    if let Some(ts_start) = &query.ts_start {
+        match parse_time_str(ts_start) {
+            Some(ts) => filter.ts_start = Some(ts),
+            None => return SearchResult::error(format!("failed to parse ts_start time string: {}", ts_start))
+        }
+    }
+    if let Some(ts_end) = &query.ts_end {
+        match parse_time_str(ts_end) {
+            Some(ts) => filter.ts_end = Some(ts),
+            None => return SearchResult::error(format!("failed to parse ts_end time string: {}", ts_end))
+        }
+    }
+    if let Some(project) = &query.project {
+        match parse_project(project) {
+            Some(p) => filter.project = Some(p),
+            None => return SearchResult::error(format!("unknown project {}: use 'routeviews', 'riperis', 'pch' or 'isolario'", project))
+        }
+    }
+    if let Some(collector_id) = &query.collector_id {
+        filter.collector_ids = collector_id.replace(' ', "").split(',')
+            .filter(|c| !c.is_empty())
+            .map(|c| c.to_string()).collect();
+    }
+    if let Some(data_type) = &query.data_type {
+        match data_type.parse::<DataType>() {
+            Ok(d) => filter.data_type = Some(d),
+            Err(e) => return SearchResult::error(e)
+        }
+    }
+    filter.peer_asn = query.peer_asn;
+    if let Some(peer_ip) = &query.peer_ip {
+        match peer_ip.parse::<IpAddr>() {
+            Ok(ip) => filter.peer_ip = Some(ip),
+            Err(_) => return SearchResult::error(format!("invalid peer_ip {}", peer_ip))
+        }
+    }
+
+    let items = match db.search_items(&filter, page, page_size).await {
+        Ok(items) => items,
+        Err(e) => return SearchResult::internal_error(e.to_string())
+    };
+    (StatusCode::OK, Json(SearchResult{
+        count: Some(items.len()),
+        page: Some(page),
+        page_size: Some(page_size),
+        error: None,
+        data: Some(items),
+    }))
+}
+
+async fn latest(State(db): State<Arc<DbConnection>>) -> Result<Json<Vec<Latest>>, (StatusCode, String)> {
+    match db.get_latest().await {
+        Ok(latest) => Ok(Json(latest)),
+        Err(e) => {
+            error!("{}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct CollectorsQuery {
+    /// filter by project name, e.g. routeviews or riperis
+    project: Option<String>,
+    /// filter by ISO 3166-1 alpha-2 country code, e.g. NL
+    country: Option<String>,
+    /// only active or only inactive collectors
+    active: Option<bool>,
+}
+
+async fn collectors(State(db): State<Arc<DbConnection>>, Query(query): Query<CollectorsQuery>) -> Result<Json<Vec<Collector>>, (StatusCode, String)> {
+    let project = match &query.project {
+        Some(project) => match parse_project(project) {
+            Some(p) => Some(p),
+            None => return Err((StatusCode::BAD_REQUEST, format!("unknown project {}: use 'routeviews', 'riperis', 'pch' or 'isolario'", project)))
+        },
+        None => None
+    };
+    let collectors = match db.get_collectors().await {
+        Ok(collectors) => collectors,
+        Err(e) => {
+            error!("{}", e);
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    };
+    Ok(Json(collectors.into_iter().filter(|c| {
+        if let Some(project) = &project {
+            if &c.project != project {
+                return false
+            }
+        }
+        if let Some(country) = &query.country {
+            match &c.meta.country {
+                Some(c) if c.eq_ignore_ascii_case(country) => {}
+                _ => return false
+            }
+        }
+        match query.active {
+            Some(active) => c.meta.active == active,
+            None => true
+        }
+    }).collect()))
+}
+
+#[derive(Debug, Deserialize)]
+struct PeersQuery {
+    /// filter by collector names, comma-separated, e.g. rrc00,route-views2
+    collector_id: Option<String>,
+    /// filter by peer ASN
+    asn: Option<u32>,
+    /// filter by peer IP address
+    ip: Option<String>,
+}
+
+async fn peers(State(db): State<Arc<DbConnection>>, Query(query): Query<PeersQuery>) -> Result<Json<Vec<Peer>>, (StatusCode, String)> {
+    let mut filter = PeerFilter { asn: query.asn, ..Default::default() };
+    if let Some(collector_id) = &query.collector_id {
+        filter.collector_ids = collector_id.replace(' ', "").split(',')
+            .filter(|c| !c.is_empty())
+            .map(|c| c.to_string()).collect();
+    }
+    if let Some(ip) = &query.ip {
+        match ip.parse::<IpAddr>() {
+            Ok(ip) => filter.ip = Some(ip),
+            Err(_) => return Err((StatusCode::BAD_REQUEST, format!("invalid ip {}", ip)))
+        }
+    }
+    match db.get_peers(&filter).await {
+        Ok(peers) => Ok(Json(peers)),
+        Err(e) => {
+            error!("{}", e);
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+
+    let _ = dotenv::dotenv();
+
+    let opts: Opts = Opts::parse();
+    let db_url = match get_db_url(opts.db_url.clone()) {
+        Ok(url) => url,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+    rt.block_on(async {
+        let db = match DbConnection::new_with_max_connections(&db_url, opts.max_connections).await {
+            Ok(db) => Arc::new(db),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        };
+
+        let app = Router::new()
+            .route("/search", get(search))
+            .route("/latest", get(latest))
+            .route("/collectors", get(collectors))
+            .route("/peers", get(peers))
+            .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any))
+            .with_state(db);
+
+        let addr: SocketAddr = format!("{}:{}", opts.host, opts.port).parse().unwrap();
+        info!("start BGPKIT Broker API at {}", addr);
+        axum::Server::bind(&addr).serve(app.into_make_service()).await.unwrap();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_time_str() {
+        let expected = NaiveDate::from_ymd_opt(2021, 10, 4).unwrap().and_hms_opt(16, 0, 0).unwrap();
+        assert_eq!(parse_time_str("1633363200"), Some(expected));
+        assert_eq!(parse_time_str("2021-10-04T16:00:00Z"), Some(expected));
+        assert_eq!(parse_time_str("2021-10-04T18:00:00+02:00"), Some(expected));
+        assert_eq!(parse_time_str("2021-10-04T16:00:00"), Some(expected));
+        assert_eq!(parse_time_str("2021-10-04"), Some(expected.date().and_hms_opt(0, 0, 0).unwrap()));
+        assert_eq!(parse_time_str("yesterday"), None);
+    }
+
+    #[test]
+    fn test_check_paging() {
+        assert!(check_paging(1, 100).is_ok());
+        assert!(check_paging(3, MAX_PAGE_SIZE).is_ok());
+        assert!(check_paging(0, 100).is_err());
+        assert!(check_paging(1, 0).is_err());
+        assert!(check_paging(1, MAX_PAGE_SIZE + 1).is_err());
+        assert!(check_paging(i64::MAX, 2).is_err());
+    }
+
+    #[test]
+    fn test_parse_project() {
+        assert_eq!(parse_project("RV"), Some(Project::RouteViews));
+        assert_eq!(parse_project("ripe-ris"), Some(Project::RipeRis));
+        assert_eq!(parse_project("PCH"), Some(Project::Pch));
+        assert_eq!(parse_project("isolario"), Some(Project::Isolario));
+        assert_eq!(parse_project("unknown"), None);
+    }
+}
@@ -1,14 +1,32 @@
+use std::str::FromStr;
 use serde::{Deserialize, Serialize};
-use crate::db::models::Collector as BCollector;
+use crate::db::models::{Collector as BCollector, CollectorMeta, Project as BProject};
+use crate::errors::ScrapeError;
+use crate::fetch::FetchConfig;
+use crate::scrapers::{LocalLayout, LocalScraper, ScraperRegistry};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     projects: Vec<Project>,
+    /// retry and rate limit settings for requests to the archives
+    #[serde(default)]
+    pub fetch: FetchConfig,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct Project {
     name: String,
+    /// archival projects are retired and no longer publish data, they are only scraped in
+    /// bootstrap and range modes
+    #[serde(default)]
+    archival: bool,
+    /// directory layout of local mirrors, `routeviews` or `riperis`; if set, the collector URLs
+    /// are local directories and the project is scraped from the filesystem
+    layout: Option<String>,
+    /// URL prefix for items of local mirrors, defaults to `file://` URLs
+    url_prefix: Option<String>,
+    /// root page listing the project's collectors, checked by the `discover` subcommand
+    discovery_url: Option<String>,
     collectors: Vec<Collector>,
 }
 
@@ -16,21 +34,58 @@ struct Project {
 struct Collector {
     id: String,
     url: String,
+    #[serde(flatten)]
+    meta: CollectorMeta,
 }
 
 impl Config {
-    pub fn to_collectors(self: &Self) -> Vec<BCollector> {
+    /// Load a collectors config JSON file.
+    pub fn from_file(path: &str) -> Result<Config, ScrapeError> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| ScrapeError::ConfigError(format!("cannot open collectors config {}: {}", path, e)))?;
+        serde_json::from_reader(file)
+            .map_err(|e| ScrapeError::ConfigError(format!("invalid collectors config {}: {}", path, e)))
+    }
+
+    pub fn to_collectors(&self) -> Vec<BCollector> {
         let mut collectors = vec![];
         for project in &self.projects {
-            assert!(["routeviews", "riperis"].contains(&project.name.as_str()));
-
             let cs: Vec<BCollector> = project.collectors.iter().map(|c| BCollector{
                 id: c.id.clone(),
-                project: project.name.clone(),
-                url: c.url.clone()
+                project: BProject::from(project.name.as_str()),
+                url: c.url.clone(),
+                meta: c.meta.clone(),
             }).collect();
             collectors.extend(cs);
         }
         collectors
     }
+
+    /// Register a [LocalScraper] for each project with a local mirror `layout`.
+    pub fn register_local_scrapers(&self, registry: &mut ScraperRegistry) -> Result<(), ScrapeError> {
+        for project in &self.projects {
+            let layout = match &project.layout {
+                Some(layout) => LocalLayout::from_str(layout.as_str())
+                    .map_err(|e| ScrapeError::ConfigError(format!("project {}: {}", project.name, e)))?,
+                None => continue
+            };
+            let url_prefix = project.url_prefix.clone();
+            registry.register(project.name.as_str(), move |mode| {
+                Box::new(LocalScraper{ mode, layout, url_prefix: url_prefix.clone() })
+            });
+        }
+        Ok(())
+    }
+
+    /// Projects with a `discovery_url`, and their discovery URLs.
+    pub fn discovery_urls(&self) -> Vec<(BProject, &str)> {
+        self.projects.iter()
+            .filter_map(|p| p.discovery_url.as_deref().map(|url| (BProject::from(p.name.as_str()), url)))
+            .collect()
+    }
+
+    /// Check if a project is marked as archival.
+    pub fn is_archival(&self, project: &str) -> bool {
+        self.projects.iter().any(|p| p.name == project && p.archival)
+    }
 }
@@ -1,11 +1,18 @@
 #![cfg(feature = "kafka")]
-/// Kafka producer utility module
+//! Kafka producer and consumer utility module
 
+use std::collections::HashSet;
 use std::time::Duration;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use rdkafka::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::message::Message;
 use rdkafka::producer::{FutureProducer, FutureRecord};
-use log::info;
-use crate::db::models::Item;
+use log::{info, warn};
+use crate::db::models::{DataType, Item};
+use crate::db::notifier::Notifier;
+use crate::errors::ScrapeError;
 
 pub struct KafkaProducer {
     producer: FutureProducer,
@@ -13,23 +20,27 @@ pub struct KafkaProducer {
 }
 
 impl KafkaProducer {
-    pub fn new(brokers: &str, topic_name: &str) -> KafkaProducer {
+    pub fn new(brokers: &str, topic_name: &str) -> Result<KafkaProducer, ScrapeError> {
         info!("initializing kafka producer with broker: {} , topic: {}", brokers, topic_name);
         let producer = ClientConfig::new()
             .set("bootstrap.servers", brokers)
             .set("message.timeout.ms", "5000")
-            .create().unwrap();
+            .create()?;
 
-        KafkaProducer{
+        Ok(KafkaProducer{
             producer,
             topic: topic_name.to_string()
-        }
+        })
     }
 
-    pub(crate) async fn produce(&self, items: &Vec<Item>) {
+}
+
+#[async_trait]
+impl Notifier for KafkaProducer {
+    async fn notify(&self, items: &[Item]) -> Result<(), ScrapeError> {
         for item in items {
             let payload = serde_json::to_string(item).unwrap();
-            let _ = self.producer
+            self.producer
                 .send(
                     FutureRecord::to(&self.topic)
                         .payload(&payload)
@@ -37,14 +48,106 @@ impl KafkaProducer {
                         // .headers(OwnedHeaders::new().add("header_key", "header_value")),
                     Duration::from_secs(0),
                 )
-                .await;
+                .await
+                .map_err(|(e, _)| ScrapeError::from(e))?;
         }
+        Ok(())
+    }
+}
+
+/// Kafka consumer of new file notifications sent by [KafkaProducer].
+///
+/// ```no_run
+/// use futures::StreamExt;
+/// use bgpkit_broker_backend::db::kafka::KafkaConsumer;
+/// use bgpkit_broker_backend::db::models::DataType;
+///
+/// # async fn run() {
+/// let consumer = KafkaConsumer::new("127.0.0.1:9092", "broker-new-files", "my-pipeline").unwrap()
+///     .with_collectors(&["rrc00", "route-views2"])
+///     .with_data_types(&[DataType::BgpTableDump]);
+/// let mut items = consumer.stream();
+/// while let Some(Ok(item)) = items.next().await {
+///     println!("new file {}", item.url);
+///     consumer.commit().unwrap();
+/// }
+/// # }
+/// ```
+pub struct KafkaConsumer {
+    consumer: StreamConsumer,
+    collectors: Option<HashSet<String>>,
+    data_types: Option<HashSet<DataType>>,
+}
+
+impl KafkaConsumer {
+    /// Create a consumer subscribed to a topic as part of a consumer group.
+    ///
+    /// Offsets are not committed automatically, call [KafkaConsumer::commit] once the received
+    /// items are processed to resume from there after a restart.
+    pub fn new(brokers: &str, topic_name: &str, group_id: &str) -> Result<KafkaConsumer, ScrapeError> {
+        info!("initializing kafka consumer with broker: {} , topic: {}, group: {}", brokers, topic_name, group_id);
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", brokers)
+            .set("group.id", group_id)
+            .set("enable.auto.commit", "false")
+            .create()?;
+        consumer.subscribe(&[topic_name])?;
+
+        Ok(KafkaConsumer{
+            consumer,
+            collectors: None,
+            data_types: None,
+        })
+    }
+
+    /// Only yield items of these collectors.
+    pub fn with_collectors(mut self, collectors: &[&str]) -> KafkaConsumer {
+        self.collectors = Some(collectors.iter().map(|c| c.to_string()).collect());
+        self
+    }
+
+    /// Only yield items of these data types.
+    pub fn with_data_types(mut self, data_types: &[DataType]) -> KafkaConsumer {
+        self.data_types = Some(data_types.iter().copied().collect());
+        self
+    }
+
+    fn matches(&self, item: &Item) -> bool {
+        self.collectors.as_ref().map(|c| c.contains(&item.collector_id)).unwrap_or(true)
+            && self.data_types.as_ref().map(|d| d.contains(&item.data_type)).unwrap_or(true)
+    }
+
+    /// Stream of new items matching the filters.
+    ///
+    /// Messages that are not valid items are skipped with a warning; Kafka errors are yielded
+    /// as errors.
+    pub fn stream(&self) -> impl Stream<Item=Result<Item, ScrapeError>> + '_ {
+        self.consumer.stream().filter_map(move |msg| {
+            let item = match msg {
+                Ok(msg) => match msg.payload().map(serde_json::from_slice::<Item>) {
+                    Some(Ok(item)) if self.matches(&item) => Some(Ok(item)),
+                    Some(Err(e)) => {
+                        warn!("skipping invalid message at offset {}: {}", msg.offset(), e);
+                        None
+                    }
+                    _ => None
+                },
+                Err(e) => Some(Err(ScrapeError::from(e)))
+            };
+            futures::future::ready(item)
+        })
+    }
+
+    /// Commit the offsets of all messages received so far.
+    pub fn commit(&self) -> Result<(), ScrapeError> {
+        self.consumer.commit_consumer_state(CommitMode::Sync)?;
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use chrono::NaiveDateTime;
+    use chrono::DateTime;
     use super::*;
 
     #[tokio::test]
@@ -52,26 +155,46 @@ mod tests {
         env_logger::init();
         let items = vec![
             Item {
-                ts_start: NaiveDateTime::from_timestamp(1658514053, 0),
-                ts_end: NaiveDateTime::from_timestamp(1658514054, 0),
+                ts_start: DateTime::from_timestamp(1658514053, 0).unwrap().naive_utc(),
+                ts_end: DateTime::from_timestamp(1658514054, 0).unwrap().naive_utc(),
                 collector_id: "rrc00".to_string(),
-                data_type: "rib".to_string(),
+                data_type: DataType::BgpTableDump,
                 url: "http://testurl.com".to_string(),
                 rough_size: 0,
                 exact_size: 0
             },
             Item {
-                ts_start: NaiveDateTime::from_timestamp(1658514053, 0),
-                ts_end: NaiveDateTime::from_timestamp(1658514054, 0),
+                ts_start: DateTime::from_timestamp(1658514053, 0).unwrap().naive_utc(),
+                ts_end: DateTime::from_timestamp(1658514054, 0).unwrap().naive_utc(),
                 collector_id: "rrc01".to_string(),
-                data_type: "rib".to_string(),
+                data_type: DataType::BgpTableDump,
                 url: "http://testurl.com".to_string(),
                 rough_size: 0,
                 exact_size: 0
             },
         ];
 
-        let producer = KafkaProducer::new("127.0.0.1:9092", "test-kafka");
-        producer.produce(&items).await;
+        let producer = KafkaProducer::new("127.0.0.1:9092", "test-kafka").unwrap();
+        let _ = producer.notify(&items).await;
+    }
+
+    #[tokio::test]
+    async fn test_consumer_filters() {
+        let item = Item {
+            ts_start: DateTime::from_timestamp(1658514053, 0).unwrap().naive_utc(),
+            ts_end: DateTime::from_timestamp(1658514054, 0).unwrap().naive_utc(),
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpTableDump,
+            url: "http://testurl.com".to_string(),
+            rough_size: 0,
+            exact_size: 0
+        };
+
+        let consumer = KafkaConsumer::new("127.0.0.1:9092", "test-kafka", "test-group").unwrap();
+        assert!(consumer.matches(&item));
+        let consumer = consumer.with_collectors(&["rrc00", "rrc01"]);
+        assert!(consumer.matches(&item));
+        let consumer = consumer.with_data_types(&[DataType::BgpUpdate]);
+        assert!(!consumer.matches(&item));
     }
 }
\ No newline at end of file
@@ -0,0 +1,179 @@
+//! Embedded schema migrations.
+//!
+//! The Postgres migrations in `migrations/` and the SQLite migrations in `migrations-sqlite/` are
+//! embedded in the binary. Applied versions are tracked in `__diesel_schema_migrations`, so the
+//! databases stay compatible with the diesel CLI.
+
+use log::{info, warn};
+use sqlx::{AnyPool, Executor, Row};
+use crate::errors::ScrapeError;
+
+pub struct Migration {
+    /// diesel version, the directory name's timestamp without separators
+    pub version: &'static str,
+    /// directory name of the migration
+    pub name: &'static str,
+    pub up: &'static str,
+    pub down: &'static str,
+}
+
+macro_rules! migration {
+    ($root:literal, $version:literal, $name:literal) => {
+        Migration {
+            version: $version,
+            name: $name,
+            up: include_str!(concat!("../../", $root, "/", $name, "/up.sql")),
+            down: include_str!(concat!("../../", $root, "/", $name, "/down.sql")),
+        }
+    };
+}
+
+const POSTGRES_MIGRATIONS: &[Migration] = &[
+    migration!("migrations", "20220413040151", "2022-04-13-040151_broker"),
+    migration!("migrations", "20261018000001", "2026-10-18-000001_item_verification"),
+    migration!("migrations", "20261018000002", "2026-10-18-000002_listing_validators"),
+    migration!("migrations", "20261018000003", "2026-10-18-000003_latest_files"),
+    migration!("migrations", "20261018000004", "2026-10-18-000004_collector_metadata"),
+    migration!("migrations", "20261018000005", "2026-10-18-000005_peers"),
+    migration!("migrations", "20261018000006", "2026-10-18-000006_item_not_found_count"),
+];
+
+#[cfg(feature = "sqlite")]
+const SQLITE_MIGRATIONS: &[Migration] = &[
+    migration!("migrations-sqlite", "20220413040151", "2022-04-13-040151_broker"),
+    migration!("migrations-sqlite", "20261018000001", "2026-10-18-000001_item_verification"),
+    migration!("migrations-sqlite", "20261018000002", "2026-10-18-000002_listing_validators"),
+    migration!("migrations-sqlite", "20261018000003", "2026-10-18-000003_latest_files"),
+    migration!("migrations-sqlite", "20261018000004", "2026-10-18-000004_collector_metadata"),
+    migration!("migrations-sqlite", "20261018000005", "2026-10-18-000005_peers"),
+    migration!("migrations-sqlite", "20261018000006", "2026-10-18-000006_item_not_found_count"),
+];
+
+/// Get the migrations of the database's backend, in order.
+pub fn migrations(pool: &AnyPool) -> &'static [Migration] {
+    match pool.any_kind() {
+        #[cfg(feature = "sqlite")]
+        sqlx::any::AnyKind::Sqlite => SQLITE_MIGRATIONS,
+        _ => POSTGRES_MIGRATIONS,
+    }
+}
+
+async fn create_migrations_table(pool: &AnyPool) -> Result<(), ScrapeError> {
+    pool.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS __diesel_schema_migrations (
+            version VARCHAR(50) PRIMARY KEY NOT NULL,
+            run_on TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+        )
+        "#
+    ).await?;
+    Ok(())
+}
+
+/// Get the applied migration versions. A database without the migrations table has none.
+pub async fn applied_versions(pool: &AnyPool) -> Result<Vec<String>, ScrapeError> {
+    let rows = match sqlx::query("SELECT version FROM __diesel_schema_migrations ORDER BY version")
+        .fetch_all(pool).await {
+        Ok(rows) => rows,
+        Err(sqlx::Error::Database(_)) => return Ok(vec![]),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(rows.iter().map(|r| r.try_get::<String, _>("version")).collect::<Result<Vec<String>, _>>()?)
+}
+
+/// Get all migrations of the database's backend, and whether each one is applied.
+pub async fn status(pool: &AnyPool) -> Result<Vec<(&'static Migration, bool)>, ScrapeError> {
+    let applied = applied_versions(pool).await?;
+    Ok(migrations(pool).iter()
+        .map(|m| (m, applied.iter().any(|v| v == m.version)))
+        .collect())
+}
+
+/// Apply all pending migrations in order, each in a transaction, and return them.
+pub async fn run_pending(pool: &AnyPool) -> Result<Vec<&'static Migration>, ScrapeError> {
+    create_migrations_table(pool).await?;
+    let mut applied = vec![];
+    for (migration, is_applied) in status(pool).await? {
+        if is_applied {
+            continue
+        }
+        info!("applying migration {}", migration.name);
+        let mut tx = pool.begin().await?;
+        tx.execute(migration.up).await?;
+        sqlx::query("INSERT INTO __diesel_schema_migrations(version) VALUES ($1)")
+            .bind(migration.version)
+            .execute(&mut tx).await?;
+        tx.commit().await?;
+        applied.push(migration);
+    }
+    Ok(applied)
+}
+
+/// Revert the most recently applied migration, if any, and return it.
+pub async fn revert_last(pool: &AnyPool) -> Result<Option<&'static Migration>, ScrapeError> {
+    let last = match status(pool).await?.into_iter().rev().find(|(_, is_applied)| *is_applied) {
+        Some((migration, _)) => migration,
+        None => return Ok(None),
+    };
+    info!("reverting migration {}", last.name);
+    let mut tx = pool.begin().await?;
+    tx.execute(last.down).await?;
+    sqlx::query("DELETE FROM __diesel_schema_migrations WHERE version=$1")
+        .bind(last.version)
+        .execute(&mut tx).await?;
+    tx.commit().await?;
+    Ok(Some(last))
+}
+
+/// Check that all migrations known to this binary are applied.
+pub async fn check_schema(pool: &AnyPool) -> Result<(), ScrapeError> {
+    let applied = applied_versions(pool).await?;
+    let known = migrations(pool);
+    let pending = known.iter()
+        .filter(|m| !applied.iter().any(|v| v == m.version))
+        .map(|m| m.name)
+        .collect::<Vec<&str>>();
+    if !pending.is_empty() {
+        return Err(ScrapeError::DatabaseError(format!(
+            "database schema is out of date, pending migrations: {}; run `bgpkit-broker-updater migrate up`",
+            pending.join(", ")
+        )))
+    }
+    let unknown = applied.iter()
+        .filter(|v| !known.iter().any(|m| m.version == v.as_str()))
+        .map(|v| v.as_str())
+        .collect::<Vec<&str>>();
+    if !unknown.is_empty() {
+        warn!("database has migrations unknown to this version applied: {}", unknown.join(", "));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::db::connect_pool;
+
+    #[tokio::test]
+    async fn test_migrations() {
+        let pool = connect_pool("sqlite::memory:", 1).await.unwrap();
+        assert!(check_schema(&pool).await.is_err());
+        assert!(status(&pool).await.unwrap().iter().all(|(_, applied)| !applied));
+
+        assert_eq!(run_pending(&pool).await.unwrap().len(), SQLITE_MIGRATIONS.len());
+        assert!(run_pending(&pool).await.unwrap().is_empty());
+        check_schema(&pool).await.unwrap();
+
+        let reverted = revert_last(&pool).await.unwrap().unwrap();
+        assert_eq!(reverted.name, "2026-10-18-000006_item_not_found_count");
+        assert!(check_schema(&pool).await.is_err());
+        // the reverted schema still serves the items
+        pool.execute("SELECT * FROM items").await.unwrap();
+        assert!(pool.execute("SELECT not_found_count FROM items").await.is_err());
+
+        let applied = run_pending(&pool).await.unwrap();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].version, "20261018000006");
+        check_schema(&pool).await.unwrap();
+    }
+}
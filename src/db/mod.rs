@@ -1,34 +1,84 @@
 pub mod models;
+pub mod migrations;
 pub mod kafka;
-
-use std::collections::HashSet;
+pub mod notifier;
+pub mod webhook;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::env;
 use std::iter::FromIterator;
-use chrono::NaiveDateTime;
+use std::net::AddrParseError;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
 
-use log::info;
-use sqlx::{ConnectOptions, Executor, PgPool, Postgres, QueryBuilder, Row};
-use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgRow};
+use log::{info, warn};
+use sqlx::{Any, AnyPool, ConnectOptions, Executor, Row};
+use sqlx::any::{AnyArguments, AnyConnectOptions, AnyPoolOptions, AnyRow};
+use sqlx::query::QueryAs;
+use sqlx::postgres::PgConnectOptions;
 
-use crate::db::models::{Collector, Item};
+use crate::db::models::{Collector, CollectorMeta, DataType, Item, ItemFilter, Latest, Peer, PeerFilter};
+use crate::db::notifier::Notifier;
+use crate::errors::ScrapeError;
+use crate::fetch::Validators;
 
 #[cfg(feature = "kafka")]
 use crate::db::kafka::KafkaProducer;
 
-const CHUNK_SIZE: usize = 60_000;
+/// Consecutive 404 responses after which an item is flagged as missing, so that a temporarily
+/// unavailable file is not hidden for good.
+pub const MISSING_AFTER_NOT_FOUND: i32 = 3;
+
+/// Maximum number of bind parameters per statement, within the limits of both Postgres (65535)
+/// and SQLite (32766).
+const CHUNK_SIZE: usize = 30_000;
 
 
 pub struct DbConnection {
-    pub pool: PgPool,
+    pub pool: AnyPool,
 
-    #[cfg(feature = "kafka")]
-    pub kafka: Option<KafkaProducer>,
+    /// Sinks notified with newly inserted items
+    pub notifiers: Vec<Box<dyn Notifier>>,
 }
 
 
-fn url_to_options(db_url: &str, disable_prepare: bool, disable_logging: bool) -> PgConnectOptions {
-    let parsed = url::Url::parse(db_url).unwrap();
+/// Get the database URL, with the given URL taking precedence over environment variables.
+///
+/// If no URL is given, `DATABASE_URL` is used if set, otherwise the URL is assembled from
+/// `POSTGRES_HOST`, `POSTGRES_PASSWORD`, `POSTGRES_USER` and `POSTGRES_DB`.
+///
+/// URLs starting with `sqlite:` (e.g. `sqlite://broker.sqlite3`) select the embedded SQLite
+/// backend, available with the `sqlite` feature.
+pub fn get_db_url(db_url: Option<String>) -> Result<String, ScrapeError> {
+    if let Some(url) = db_url {
+        return Ok(url)
+    }
+    match env::var("DATABASE_URL") {
+        Ok(url) => {
+            // DATABASE_URL already set, use the one specified
+            Ok(url)
+        }
+        Err(_) => {
+            let get_var = |name: &str| env::var(name)
+                .map_err(|_| ScrapeError::ConfigError(format!("{} must be set when DATABASE_URL is not", name)));
+            let host = get_var("POSTGRES_HOST")?;
+            let password = get_var("POSTGRES_PASSWORD")?;
+            let user = get_var("POSTGRES_USER")?;
+            let db = get_var("POSTGRES_DB")?;
+            Ok(format!("postgres://{}:{}@{}/{}", user, password, host, db))
+        }
+    }
+}
+
+fn url_to_options(db_url: &str, disable_prepare: bool, disable_logging: bool) -> Result<PgConnectOptions, ScrapeError> {
+    let parsed = url::Url::parse(db_url)
+        .map_err(|e| ScrapeError::ConfigError(format!("invalid database URL {}: {}", db_url, e)))?;
+    let host = parsed.host()
+        .ok_or_else(|| ScrapeError::ConfigError(format!("database URL {} has no host", db_url)))?;
     let mut opts = PgConnectOptions::new()
-        .host(parsed.host().unwrap().to_string().as_str());
+        .host(host.to_string().as_str());
     if parsed.username()!="" {
         opts = opts.username(parsed.username());
     }
@@ -41,8 +91,7 @@ fn url_to_options(db_url: &str, disable_prepare: bool, disable_logging: bool) ->
         opts = opts.port(port);
     }
 
-    let parts = db_url.split("/").collect::<Vec<&str>>();
-    let db_name = parts.into_iter().last().unwrap();
+    let db_name = parsed.path().trim_start_matches('/');
     opts = opts.database(db_name);
 
     if disable_prepare {
@@ -53,69 +102,245 @@ fn url_to_options(db_url: &str, disable_prepare: bool, disable_logging: bool) ->
         opts.disable_statement_logging();
     }
 
-    opts
+    Ok(opts)
 }
 
-impl DbConnection {
-    #[cfg(feature = "kafka")]
-    pub async fn new(db_url: &str) -> DbConnection {
-        let options = url_to_options(db_url, true, true);
-        let pool = PgPoolOptions::new().max_connections(1).connect_with(options).await.unwrap();
-        DbConnection{ pool, kafka: None }
+fn is_sqlite_url(db_url: &str) -> bool {
+    db_url.starts_with("sqlite:")
+}
+
+fn any_options(db_url: &str) -> Result<AnyConnectOptions, ScrapeError> {
+    if is_sqlite_url(db_url) {
+        #[cfg(feature = "sqlite")]
+        return Ok(sqlite::url_to_options(db_url)?.into());
+        #[cfg(not(feature = "sqlite"))]
+        return Err(ScrapeError::ConfigError(format!("{} is a SQLite database, rebuild with the `sqlite` feature to use it", db_url)));
+    }
+    Ok(url_to_options(db_url, true, true)?.into())
+}
+
+/// Connect to a Postgres or SQLite database, without checking its schema. SQLite database files
+/// are created if missing.
+pub async fn connect_pool(db_url: &str, max_connections: u32) -> Result<AnyPool, ScrapeError> {
+    Ok(AnyPoolOptions::new().max_connections(max_connections).connect_with(any_options(db_url)?).await?)
+}
+
+/// Connect to a database with an up-to-date schema. SQLite databases are migrated to the latest
+/// schema, Postgres databases must be migrated with `bgpkit-broker-updater migrate up`.
+async fn connect_checked(db_url: &str, max_connections: u32) -> Result<AnyPool, ScrapeError> {
+    let pool = connect_pool(db_url, max_connections).await?;
+    if is_sqlite_url(db_url) {
+        migrations::run_pending(&pool).await?;
+    } else {
+        migrations::check_schema(&pool).await?;
+    }
+    Ok(pool)
+}
+
+/// Parse a `YYYY.MM` month into the time range from its first day to 31 days later.
+fn month_range(month_str: &str) -> Result<(NaiveDateTime, NaiveDateTime), ScrapeError> {
+    let start_ts = NaiveDateTime::parse_from_str(format!("{}.01T00:00:00", month_str).as_str(), "%Y.%m.%dT%H:%M:%S")
+        .map_err(|e| ScrapeError::ParseError(format!("parsing month {} failed: {}", month_str, e)))?;
+    Ok((start_ts, start_ts + chrono::Duration::days(31)))
+}
+
+/// Build `count` groups of `width` positional placeholders, e.g. `($1, $2), ($3, $4)`.
+///
+/// `QueryBuilder<Any>` emits `?` placeholders which Postgres does not accept, while `$N` works
+/// for both backends.
+fn values_placeholders(count: usize, width: usize) -> String {
+    (0..count).map(|row| {
+        let values = (1..=width).map(|col| format!("${}", row * width + col)).collect::<Vec<String>>().join(", ");
+        format!("({})", values)
+    }).collect::<Vec<String>>().join(", ")
+}
+
+/// Build the SQL conditions of an item filter, each starting with ` AND `, with placeholders
+/// numbered from `$1`, and return them with the number of placeholders.
+fn filter_conditions(filter: &ItemFilter) -> (String, usize) {
+    let mut sql = String::new();
+    let mut param = 0;
+    let mut next_param = || {
+        param += 1;
+        format!("${}", param)
+    };
+    sql.push_str(" AND NOT missing");
+    if filter.ts_start.is_some() {
+        sql.push_str(format!(" AND ts_end >= {}", next_param()).as_str());
+    }
+    if filter.ts_end.is_some() {
+        sql.push_str(format!(" AND ts_start <= {}", next_param()).as_str());
+    }
+    if filter.data_type.is_some() {
+        sql.push_str(format!(" AND data_type = {}", next_param()).as_str());
+    }
+    if filter.project.is_some() {
+        sql.push_str(format!(" AND collector_id IN (SELECT id FROM collectors WHERE project = {})", next_param()).as_str());
+    }
+    if !filter.collector_ids.is_empty() {
+        let params = filter.collector_ids.iter().map(|_| next_param()).collect::<Vec<String>>();
+        sql.push_str(format!(" AND collector_id IN ({})", params.join(", ")).as_str());
     }
+    if filter.peer_asn.is_some() {
+        sql.push_str(peer_condition(format!("p.peer_asn = {}", next_param()).as_str()).as_str());
+    }
+    if filter.peer_ip.is_some() {
+        sql.push_str(peer_condition(format!("p.peer_ip = {}", next_param()).as_str()).as_str());
+    }
+    (sql, param)
+}
+
+/// Condition matching items of collectors that peered with the peers selected by `peer` at the
+/// time, i.e. items starting from the first RIB dump listing a peer up to the next indexed dump
+/// not listing it anymore. Items after the newest indexed dump listing a peer match as long as no
+/// later dump is indexed, so the latest files of current peers are included.
+fn peer_condition(peer: &str) -> String {
+    format!(
+        " AND EXISTS (SELECT 1 FROM peers p WHERE p.collector_id = items.collector_id AND {} AND items.ts_start >= p.first_seen \
+         AND NOT EXISTS (SELECT 1 FROM items r WHERE r.collector_id = p.collector_id AND r.peers_indexed AND r.ts_start > p.last_seen AND r.ts_start <= items.ts_start))",
+        peer
+    )
+}
 
-    #[cfg(not(feature = "kafka"))]
-    pub async fn new(db_url: &str) -> DbConnection {
+/// Bind the values of an item filter in the same order as the placeholders of
+/// [filter_conditions].
+fn bind_filter<'q>(mut query: QueryAs<'q, Any, Item, AnyArguments<'q>>, filter: &'q ItemFilter) -> QueryAs<'q, Any, Item, AnyArguments<'q>> {
+    if let Some(ts_start) = filter.ts_start {
+        query = query.bind(ts_start);
+    }
+    if let Some(ts_end) = filter.ts_end {
+        query = query.bind(ts_end);
+    }
+    if let Some(data_type) = filter.data_type {
+        query = query.bind(data_type);
+    }
+    if let Some(project) = &filter.project {
+        query = query.bind(project);
+    }
+    for collector_id in &filter.collector_ids {
+        query = query.bind(collector_id.as_str());
+    }
+    if let Some(peer_asn) = filter.peer_asn {
+        query = query.bind(i64::from(peer_asn));
+    }
+    if let Some(peer_ip) = filter.peer_ip {
+        query = query.bind(peer_ip.to_string());
+    }
+    query
+}
+
+impl DbConnection {
+    pub async fn new(db_url: &str) -> Result<DbConnection, ScrapeError> {
         info!("connecting to BGPKIT Broker database at {}", db_url);
-        let options = url_to_options(db_url, true, true);
-        let pool = PgPoolOptions::new().max_connections(1).connect_with(options).await.unwrap();
-        DbConnection{ pool }
+        let pool = connect_checked(db_url, 1).await?;
+        Ok(DbConnection{ pool, notifiers: vec![] })
+    }
+
+    /// Connect with a pool of up to `max_connections` connections, used by the API to serve
+    /// concurrent requests.
+    pub async fn new_with_max_connections(db_url: &str, max_connections: u32) -> Result<DbConnection, ScrapeError> {
+        info!("connecting to BGPKIT Broker database at {} with up to {} connections", db_url, max_connections);
+        let pool = connect_checked(db_url, max_connections).await?;
+        Ok(DbConnection{ pool, notifiers: vec![] })
     }
 
     #[cfg(feature="kafka")]
-    pub async fn new_with_kafka(db_url: &str, kafka_brokers: Option<&str>, kafka_topic: Option<&str>) -> DbConnection {
-        info!("connecting to BGPKIT Broker database at {}", db_url);
-        let options = url_to_options(db_url, true, true);
-        let pool = PgPoolOptions::new().max_connections(1).connect_with(options).await.unwrap();
-        let kafka: Option<KafkaProducer> =
-            if kafka_brokers.is_some() && kafka_topic.is_some() {
-                let broker = kafka_brokers.unwrap();
-                let topic = kafka_topic.unwrap();
-                info!("connecting to kafka broker {} with topic {}", broker, topic);
-                Some(KafkaProducer::new(broker, topic))
-            } else {
-                None
-            };
-        DbConnection{ pool, kafka }
+    pub async fn new_with_kafka(db_url: &str, kafka_brokers: Option<&str>, kafka_topic: Option<&str>) -> Result<DbConnection, ScrapeError> {
+        let conn = DbConnection::new(db_url).await?;
+        if let (Some(broker), Some(topic)) = (kafka_brokers, kafka_topic) {
+            info!("connecting to kafka broker {} with topic {}", broker, topic);
+            Ok(conn.with_notifier(Box::new(KafkaProducer::new(broker, topic)?)))
+        } else {
+            Ok(conn)
+        }
     }
 
-    pub async fn insert_collectors(&self, entries: &Vec<Collector>){
+    /// Add a sink to notify with newly inserted items.
+    pub fn with_notifier(mut self, notifier: Box<dyn Notifier>) -> DbConnection {
+        self.notifiers.push(notifier);
+        self
+    }
+
+    /// Insert collectors, updating the project, URL and metadata of existing ones.
+    pub async fn insert_collectors(&self, entries: &[Collector]) -> Result<(), ScrapeError> {
         info!("inserting collectors info");
 
-        let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
-            "INSERT INTO collectors(id, project, url) "
-        );
-        query_builder.push_values(entries, |mut b, collector| {
-            b.push_bind(collector.id.as_str())
-                .push_bind(collector.project.as_str())
-                .push_bind(collector.url.as_str());
-        });
-        query_builder.push(
-            " ON CONFLICT DO NOTHING "
+        if entries.is_empty() {
+            return Ok(())
+        }
+        let sql = format!(
+            r#"
+            INSERT INTO collectors(id, project, url, country, location, latitude, longitude, org, ip_family,
+                                   activated_on, decommissioned_on, rib_interval_minutes, update_interval_minutes, active)
+            VALUES {}
+            ON CONFLICT (id) DO UPDATE
+            SET project=excluded.project, url=excluded.url, country=excluded.country, location=excluded.location,
+                latitude=excluded.latitude, longitude=excluded.longitude, org=excluded.org, ip_family=excluded.ip_family,
+                activated_on=excluded.activated_on, decommissioned_on=excluded.decommissioned_on,
+                rib_interval_minutes=excluded.rib_interval_minutes, update_interval_minutes=excluded.update_interval_minutes,
+                active=excluded.active
+            "#,
+            values_placeholders(entries.len(), 14)
         );
-        let query = query_builder.build();
-        let _res = self.pool.execute(query).await;
+        let mut query = sqlx::query(sql.as_str());
+        for collector in entries {
+            let meta = &collector.meta;
+            query = query
+                .bind(collector.id.as_str())
+                .bind(&collector.project)
+                .bind(collector.url.as_str())
+                .bind(meta.country.as_deref())
+                .bind(meta.location.as_deref())
+                .bind(meta.latitude)
+                .bind(meta.longitude)
+                .bind(meta.org.as_deref())
+                .bind(meta.ip_family.as_deref())
+                .bind(meta.activated_on)
+                .bind(meta.decommissioned_on)
+                .bind(meta.rib_interval_minutes)
+                .bind(meta.update_interval_minutes)
+                .bind(meta.active);
+        }
+        self.pool.execute(query).await?;
+        Ok(())
     }
 
-    pub async fn count_records_in_month(&self, collector: &str, month_str: &str) -> i64 {
+    /// Get all collectors with their metadata, ordered by ID.
+    pub async fn get_collectors(&self) -> Result<Vec<Collector>, ScrapeError> {
+        let collectors = sqlx::query(
+            r#"
+            SELECT id, project, url, country, location, latitude, longitude, org, ip_family,
+                   activated_on, decommissioned_on, rib_interval_minutes, update_interval_minutes, active
+            FROM collectors
+            ORDER BY id
+            "#
+        )
+            .fetch_all(&self.pool).await?
+            .into_iter().map(|row: AnyRow| {
+            Ok(Collector{
+                id: row.try_get("id")?,
+                project: row.try_get("project")?,
+                url: row.try_get("url")?,
+                meta: CollectorMeta{
+                    country: row.try_get("country")?,
+                    location: row.try_get("location")?,
+                    latitude: row.try_get("latitude")?,
+                    longitude: row.try_get("longitude")?,
+                    org: row.try_get("org")?,
+                    ip_family: row.try_get("ip_family")?,
+                    activated_on: row.try_get("activated_on")?,
+                    decommissioned_on: row.try_get("decommissioned_on")?,
+                    rib_interval_minutes: row.try_get("rib_interval_minutes")?,
+                    update_interval_minutes: row.try_get("update_interval_minutes")?,
+                    active: row.try_get("active")?,
+                }
+            })
+        }).collect::<Result<Vec<Collector>, sqlx::Error>>()?;
+        Ok(collectors)
+    }
 
-        let start_ts = match NaiveDateTime::parse_from_str(format!("{}.01T00:00:00", month_str).as_str(), "%Y.%m.%dT%H:%M:%S"){
-            Ok(t) => {t}
-            Err(e) => {
-                panic!("parsing {} failed: {}", month_str, e.to_string())
-            }
-        };
-        let end_ts = start_ts + chrono::Duration::days(31);
+    pub async fn count_records_in_month(&self, collector: &str, month_str: &str) -> Result<i64, ScrapeError> {
+        let (start_ts, end_ts) = month_range(month_str)?;
 
         let records = sqlx::query(
            r#"
@@ -127,22 +352,16 @@ impl DbConnection {
            "#,
        )
             .bind(collector)
-            .bind(&start_ts)
-            .bind(&end_ts)
+            .bind(start_ts)
+            .bind(end_ts)
             .fetch_one(&self.pool)
-            .await.unwrap();
+            .await?;
 
-        records.try_get("c").unwrap()
+        Ok(records.try_get("c")?)
     }
 
-    pub async fn get_urls_in_month(&self, collector: &str, month_str: &str) -> HashSet<String> {
-        let start_ts = match NaiveDateTime::parse_from_str(format!("{}.01T00:00:00", month_str).as_str(), "%Y.%m.%dT%H:%M:%S"){
-            Ok(t) => {t}
-            Err(e) => {
-                panic!("parsing {} failed: {}", month_str, e.to_string())
-            }
-        };
-        let end_ts = start_ts + chrono::Duration::days(31);
+    pub async fn get_urls_in_month(&self, collector: &str, month_str: &str) -> Result<HashSet<String>, ScrapeError> {
+        let (start_ts, end_ts) = month_range(month_str)?;
         let urls = sqlx::query(
             r#"
            SELECT url
@@ -153,90 +372,438 @@ impl DbConnection {
            "#,
         )
             .bind(collector)
-            .bind(&start_ts)
-            .bind(&end_ts)
-            .fetch_all(&self.pool).await.unwrap()
-            .iter().map(|r|r.get::<String,_>("url").to_string()).collect::<Vec<String>>();
+            .bind(start_ts)
+            .bind(end_ts)
+            .fetch_all(&self.pool).await?
+            .iter().map(|r|r.try_get::<String,_>("url")).collect::<Result<Vec<String>, _>>()?;
 
-        HashSet::from_iter(urls.into_iter())
+        Ok(HashSet::from_iter(urls))
     }
 
-    pub async fn insert_items(&self, entries: &Vec<Item>) -> Vec<Item> {
+    /// Insert items, skipping the ones already in the database, and return the newly inserted
+    /// ones. The latest file of each collector and data type is updated in the same transaction.
+    pub async fn insert_items(&self, entries: &[Item]) -> Result<Vec<Item>, ScrapeError> {
+        let mut tx = self.pool.begin().await?;
         let mut inserted = vec![];
         for chunk in entries.chunks(CHUNK_SIZE/7){
-            let mut query_builder: QueryBuilder<Postgres> = QueryBuilder::new(
-                "INSERT INTO items(ts_start, ts_end, collector_id, data_type, url, rough_size, exact_size) "
-            );
-            query_builder.push_values(chunk, |mut b, item| {
-                b
-                    .push_bind(&item.ts_start)
-                    .push_bind(&item.ts_end)
-                    .push_bind(item.collector_id.as_str())
-                    .push_bind(item.data_type.as_str())
-                    .push_bind(item.url.as_str())
-                    .push_bind(item.rough_size)
-                    .push_bind(item.exact_size)
-                ;
-            });
-            query_builder.push(
-                " ON CONFLICT DO NOTHING "
-            );
-            query_builder.push(
-            " RETURNING *"
+            let sql = format!(
+                "INSERT INTO items(ts_start, ts_end, collector_id, data_type, url, rough_size, exact_size) VALUES {} ON CONFLICT DO NOTHING RETURNING *",
+                values_placeholders(chunk.len(), 7)
             );
-            let query = query_builder.build();
-            let res: Vec<Item> = query.fetch_all(&self.pool).await.unwrap().into_iter().map(|row: PgRow|{
-                Item{
-                    ts_start: row.try_get("ts_start").unwrap(),
-                    ts_end: row.try_get("ts_end").unwrap(),
-                    collector_id: row.try_get("collector_id").unwrap(),
-                    data_type: row.try_get("data_type").unwrap(),
-                    url: row.try_get("url").unwrap(),
-                    rough_size: row.try_get("rough_size").unwrap(),
-                    exact_size: row.try_get("exact_size").unwrap()
-                }
-            }).collect();
+            let mut query = sqlx::query(sql.as_str());
+            for item in chunk {
+                query = query
+                    .bind(item.ts_start)
+                    .bind(item.ts_end)
+                    .bind(item.collector_id.as_str())
+                    .bind(item.data_type)
+                    .bind(item.url.as_str())
+                    .bind(item.rough_size)
+                    .bind(item.exact_size);
+            }
+            let res = query.fetch_all(&mut tx).await?.into_iter().map(|row: AnyRow|{
+                Ok(Item{
+                    ts_start: row.try_get("ts_start")?,
+                    ts_end: row.try_get("ts_end")?,
+                    collector_id: row.try_get("collector_id")?,
+                    data_type: row.try_get("data_type")?,
+                    url: row.try_get("url")?,
+                    rough_size: row.try_get("rough_size")?,
+                    exact_size: row.try_get("exact_size")?
+                })
+            }).collect::<Result<Vec<Item>, sqlx::Error>>()?;
             inserted.extend(res);
         }
-        inserted
+
+        let mut latest: HashMap<(&str, DataType), &Item> = HashMap::new();
+        for item in &inserted {
+            let entry = latest.entry((item.collector_id.as_str(), item.data_type)).or_insert(item);
+            if item.ts_start > entry.ts_start {
+                *entry = item;
+            }
+        }
+        let latest = latest.into_values().collect::<Vec<&Item>>();
+        for chunk in latest.chunks(CHUNK_SIZE/4) {
+            let sql = format!(
+                r#"
+                INSERT INTO latest_files(collector_id, data_type, ts_start, item_url) VALUES {}
+                ON CONFLICT (collector_id, data_type) DO UPDATE
+                SET ts_start=excluded.ts_start, item_url=excluded.item_url
+                WHERE excluded.ts_start > latest_files.ts_start
+                "#,
+                values_placeholders(chunk.len(), 4)
+            );
+            let mut query = sqlx::query(sql.as_str());
+            for item in chunk {
+                query = query
+                    .bind(item.collector_id.as_str())
+                    .bind(item.data_type)
+                    .bind(item.ts_start)
+                    .bind(item.url.as_str());
+            }
+            query.execute(&mut tx).await?;
+        }
+
+        tx.commit().await?;
+        Ok(inserted)
     }
 
-    #[cfg(feature="kafka")]
-    pub async fn notify(&self, items: &Vec<Item>) {
-        if let Some(kafka) = &self.kafka {
-            kafka.produce(items).await;
+    /// Get the sorted start times of a collector's items of a data type within `[from, to)`.
+    pub async fn get_timestamps(&self, collector_id: &str, data_type: DataType, from: NaiveDateTime, to: NaiveDateTime) -> Result<Vec<NaiveDateTime>, ScrapeError> {
+        let timestamps = sqlx::query(
+            r#"
+            SELECT DISTINCT ts_start
+            FROM items
+            WHERE collector_id=$1 AND data_type=$2 AND ts_start >= $3 AND ts_start < $4 AND NOT missing
+            ORDER BY ts_start
+            "#
+        )
+            .bind(collector_id)
+            .bind(data_type)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool).await?
+            .iter().map(|r| r.try_get::<NaiveDateTime, _>("ts_start")).collect::<Result<Vec<NaiveDateTime>, _>>()?;
+        Ok(timestamps)
+    }
+
+    /// Get up to `limit` most recent items of a collector without exact sizes that are not known
+    /// to be missing.
+    pub async fn get_unverified_items(&self, collector_id: &str, limit: i64) -> Result<Vec<Item>, ScrapeError> {
+        let items = sqlx::query_as::<_, Item>(
+            r#"
+            SELECT ts_start, ts_end, collector_id, data_type, url, rough_size, exact_size
+            FROM items
+            WHERE collector_id=$1 AND exact_size=0 AND NOT missing
+            ORDER BY ts_start DESC
+            LIMIT $2
+            "#
+        )
+            .bind(collector_id)
+            .bind(limit)
+            .fetch_all(&self.pool).await?;
+        Ok(items)
+    }
+
+    /// Update exact sizes of items, given as pairs of URL and size.
+    pub async fn update_exact_sizes(&self, sizes: &[(String, i64)]) -> Result<(), ScrapeError> {
+        let mut tx = self.pool.begin().await?;
+        for (url, size) in sizes {
+            sqlx::query("UPDATE items SET exact_size=$1, not_found_count=0 WHERE url=$2")
+                .bind(size)
+                .bind(url.as_str())
+                .execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Count a 404 response for each item, flagging items as missing after
+    /// [MISSING_AFTER_NOT_FOUND] consecutive 404s. Missing items are left out of searches.
+    pub async fn record_not_found(&self, urls: &[String]) -> Result<(), ScrapeError> {
+        let mut tx = self.pool.begin().await?;
+        for url in urls {
+            sqlx::query("UPDATE items SET not_found_count=not_found_count+1, missing=(not_found_count+1 >= $1) WHERE url=$2")
+                .bind(MISSING_AFTER_NOT_FOUND)
+                .bind(url.as_str())
+                .execute(&mut tx).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get up to `limit` most recent RIB dumps of a collector whose peers are not indexed yet and
+    /// that are not known to be missing.
+    pub async fn get_unindexed_ribs(&self, collector_id: &str, limit: i64) -> Result<Vec<Item>, ScrapeError> {
+        let items = sqlx::query_as::<_, Item>(
+            r#"
+            SELECT ts_start, ts_end, collector_id, data_type, url, rough_size, exact_size
+            FROM items
+            WHERE collector_id=$1 AND data_type=$2 AND NOT peers_indexed AND NOT missing
+            ORDER BY ts_start DESC
+            LIMIT $3
+            "#
+        )
+            .bind(collector_id)
+            .bind(DataType::BgpTableDump)
+            .bind(limit)
+            .fetch_all(&self.pool).await?;
+        Ok(items)
+    }
+
+    /// Save the peers listed in a RIB dump and mark the dump as indexed. Peers already known
+    /// have their first and last seen times extended to cover the dump.
+    pub async fn save_rib_peers(&self, item_url: &str, peers: &[Peer]) -> Result<(), ScrapeError> {
+        let mut tx = self.pool.begin().await?;
+        for peer in peers {
+            sqlx::query(
+                r#"
+                INSERT INTO peers(collector_id, peer_asn, peer_ip, peer_bgp_id, first_seen, last_seen)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (collector_id, peer_ip, peer_asn) DO UPDATE
+                SET peer_bgp_id=CASE WHEN excluded.last_seen >= peers.last_seen THEN excluded.peer_bgp_id ELSE peers.peer_bgp_id END,
+                    first_seen=CASE WHEN excluded.first_seen < peers.first_seen THEN excluded.first_seen ELSE peers.first_seen END,
+                    last_seen=CASE WHEN excluded.last_seen > peers.last_seen THEN excluded.last_seen ELSE peers.last_seen END
+                "#
+            )
+                .bind(peer.collector_id.as_str())
+                .bind(i64::from(peer.asn))
+                .bind(peer.ip.to_string())
+                .bind(peer.bgp_id.to_string())
+                .bind(peer.first_seen)
+                .bind(peer.last_seen)
+                .execute(&mut tx).await?;
+        }
+        sqlx::query("UPDATE items SET peers_indexed=TRUE, not_found_count=0 WHERE url=$1")
+            .bind(item_url)
+            .execute(&mut tx).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the peers matching a filter, ordered by collector, ASN and IP address.
+    pub async fn get_peers(&self, filter: &PeerFilter) -> Result<Vec<Peer>, ScrapeError> {
+        let mut conditions = String::new();
+        let mut param = 0;
+        if !filter.collector_ids.is_empty() {
+            let params = filter.collector_ids.iter().map(|_| {
+                param += 1;
+                format!("${}", param)
+            }).collect::<Vec<String>>();
+            conditions.push_str(format!(" AND collector_id IN ({})", params.join(", ")).as_str());
+        }
+        if filter.asn.is_some() {
+            param += 1;
+            conditions.push_str(format!(" AND peer_asn = ${}", param).as_str());
+        }
+        if filter.ip.is_some() {
+            param += 1;
+            conditions.push_str(format!(" AND peer_ip = ${}", param).as_str());
+        }
+        let sql = format!(
+            "SELECT collector_id, peer_asn, peer_ip, peer_bgp_id, first_seen, last_seen FROM peers WHERE TRUE{} ORDER BY collector_id, peer_asn, peer_ip",
+            conditions
+        );
+        let mut query = sqlx::query(sql.as_str());
+        for collector_id in &filter.collector_ids {
+            query = query.bind(collector_id.as_str());
+        }
+        if let Some(asn) = filter.asn {
+            query = query.bind(i64::from(asn));
+        }
+        if let Some(ip) = filter.ip {
+            query = query.bind(ip.to_string());
+        }
+        query.fetch_all(&self.pool).await?
+            .into_iter().map(|row: AnyRow| {
+            let asn: i64 = row.try_get("peer_asn")?;
+            let ip: String = row.try_get("peer_ip")?;
+            let bgp_id: String = row.try_get("peer_bgp_id")?;
+            let invalid = |e: String| ScrapeError::DatabaseError(format!("invalid peer {}: {}", ip, e));
+            Ok(Peer{
+                collector_id: row.try_get("collector_id")?,
+                asn: u32::try_from(asn).map_err(|e| invalid(e.to_string()))?,
+                ip: ip.parse().map_err(|e: AddrParseError| invalid(e.to_string()))?,
+                bgp_id: bgp_id.parse().map_err(|e: AddrParseError| invalid(e.to_string()))?,
+                first_seen: row.try_get("first_seen")?,
+                last_seen: row.try_get("last_seen")?,
+            })
+        }).collect()
+    }
+
+    /// Get the cache validators saved for a listing page.
+    pub async fn get_listing_validators(&self, url: &str) -> Result<Option<Validators>, ScrapeError> {
+        let row = sqlx::query("SELECT etag, last_modified FROM listing_validators WHERE url=$1")
+            .bind(url)
+            .fetch_optional(&self.pool).await?;
+        match row {
+            None => Ok(None),
+            Some(row) => Ok(Some(Validators{
+                etag: row.try_get("etag")?,
+                last_modified: row.try_get("last_modified")?,
+            }))
+        }
+    }
+
+    /// Save the cache validators of a listing page, replacing the previous ones.
+    pub async fn save_listing_validators(&self, url: &str, validators: &Validators) -> Result<(), ScrapeError> {
+        sqlx::query(
+            r#"
+            INSERT INTO listing_validators(url, etag, last_modified, updated_at) VALUES ($1, $2, $3, $4)
+            ON CONFLICT (url) DO UPDATE SET etag=excluded.etag, last_modified=excluded.last_modified, updated_at=excluded.updated_at
+            "#
+        )
+            .bind(url)
+            .bind(validators.etag.as_deref())
+            .bind(validators.last_modified.as_deref())
+            .bind(chrono::Utc::now().naive_utc())
+            .execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Search items matching the filter, ordered by `ts_start`. `page` starts from 1.
+    pub async fn search_items(&self, filter: &ItemFilter, page: i64, page_size: i64) -> Result<Vec<Item>, ScrapeError> {
+        let (conditions, params) = filter_conditions(filter);
+        let sql = format!(
+            "SELECT ts_start, ts_end, collector_id, data_type, url, rough_size, exact_size FROM items WHERE TRUE{} ORDER BY ts_start, url LIMIT ${} OFFSET ${}",
+            conditions, params + 1, params + 2
+        );
+        let query = bind_filter(sqlx::query_as::<_, Item>(sql.as_str()), filter);
+        Ok(query.bind(page_size).bind((page - 1) * page_size).fetch_all(&self.pool).await?)
+    }
+
+    /// Get the items matching a filter that start within `[from, to)`, ordered by start time.
+    pub async fn get_items_starting_in(&self, filter: &ItemFilter, from: NaiveDateTime, to: NaiveDateTime) -> Result<Vec<Item>, ScrapeError> {
+        let (conditions, params) = filter_conditions(filter);
+        let sql = format!(
+            "SELECT ts_start, ts_end, collector_id, data_type, url, rough_size, exact_size FROM items WHERE TRUE{} AND ts_start >= ${} AND ts_start < ${} ORDER BY ts_start, url",
+            conditions, params + 1, params + 2
+        );
+        let query = bind_filter(sqlx::query_as::<_, Item>(sql.as_str()), filter);
+        Ok(query.bind(from).bind(to).fetch_all(&self.pool).await?)
+    }
+
+    /// Get the start times of the first and the last item, if there are any items.
+    pub async fn get_items_time_range(&self) -> Result<Option<(NaiveDateTime, NaiveDateTime)>, ScrapeError> {
+        let row = sqlx::query("SELECT min(ts_start) AS first, max(ts_start) AS last FROM items")
+            .fetch_one(&self.pool).await?;
+        let first: Option<NaiveDateTime> = row.try_get("first")?;
+        let last: Option<NaiveDateTime> = row.try_get("last")?;
+        Ok(first.zip(last))
+    }
+
+    /// Get the months, as `[start, end)` ranges, that may contain items matching the filter, from
+    /// the first to the last month with items.
+    pub async fn get_item_months(&self, filter: &ItemFilter) -> Result<Vec<(NaiveDateTime, NaiveDateTime)>, ScrapeError> {
+        let (first, last) = match self.get_items_time_range().await? {
+            Some(range) => range,
+            None => return Ok(vec![])
+        };
+        // items overlapping the start of the filter's range may start up to a day earlier
+        let from = filter.ts_start.map(|ts| (ts - chrono::Duration::days(1)).max(first)).unwrap_or(first);
+        let to = filter.ts_end.map(|ts| ts.min(last)).unwrap_or(last);
+
+        let mut months = vec![];
+        let mut month = NaiveDate::from_ymd_opt(from.year(), from.month(), 1).unwrap();
+        while month <= to.date() {
+            let next = match month.month() {
+                12 => NaiveDate::from_ymd_opt(month.year() + 1, 1, 1).unwrap(),
+                m => NaiveDate::from_ymd_opt(month.year(), m + 1, 1).unwrap(),
+            };
+            months.push((month.and_hms_opt(0, 0, 0).unwrap(), next.and_hms_opt(0, 0, 0).unwrap()));
+            month = next;
+        }
+        Ok(months)
+    }
+
+    /// Get the latest item of each collector and data type.
+    pub async fn get_latest(&self) -> Result<Vec<Latest>, ScrapeError> {
+        let latest = sqlx::query(
+            r#"
+            SELECT "timestamp", collector_id, data_type, rough_size, exact_size, project, collector_url, item_url, active
+            FROM latest_times
+            ORDER BY collector_id, data_type
+            "#
+        )
+            .fetch_all(&self.pool).await?
+            .into_iter().map(|row: AnyRow| {
+            Ok(Latest{
+                timestamp: row.try_get("timestamp")?,
+                collector_id: row.try_get("collector_id")?,
+                data_type: row.try_get("data_type")?,
+                rough_size: row.try_get("rough_size")?,
+                exact_size: row.try_get("exact_size")?,
+                project: row.try_get("project")?,
+                collector_url: row.try_get("collector_url")?,
+                item_url: row.try_get("item_url")?,
+                active: row.try_get("active")?,
+            })
+        }).collect::<Result<Vec<Latest>, sqlx::Error>>()?;
+        Ok(latest)
+    }
+
+    /// Notify all sinks of newly inserted items. Failures are logged, the items are already in
+    /// the database at this point.
+    pub async fn notify(&self, items: &[Item]) {
+        if items.is_empty() {
+            return
+        }
+        for notifier in &self.notifiers {
+            if let Err(e) = notifier.notify(items).await {
+                warn!("failed to notify {} new items: {}", items.len(), e);
+            }
         }
     }
 }
 
+/// Shared setup of the tests working on a database.
+#[cfg(all(test, feature = "sqlite"))]
+pub(crate) mod test_utils {
+    use chrono::{Duration, NaiveDateTime};
+    use crate::db::models::{Collector, DataType, Item, Project};
+
+    /// The RIPE RIS rrc00 collector.
+    pub(crate) fn test_collector() -> Collector {
+        Collector{
+            id: "rrc00".to_string(),
+            project: Project::RipeRis,
+            url: "https://data.ris.ripe.net/rrc00".to_string(),
+            meta: Default::default()
+        }
+    }
+
+    /// An in-memory database with [test_collector] and its four 5-minute updates files from
+    /// 2022-09-30 23:50 to 2022-10-01 00:10, two in each month.
+    pub(crate) async fn seeded_db() -> (super::DbConnection, Vec<Item>) {
+        let db = super::DbConnection::new("sqlite::memory:").await.unwrap();
+        db.insert_collectors(&[test_collector()]).await.unwrap();
+        let ts = NaiveDateTime::parse_from_str("2022-09-30T23:50:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        let items = (0..4).map(|i| Item{
+            ts_start: ts + Duration::minutes(5 * i),
+            ts_end: ts + Duration::minutes(5 * i + 5),
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpUpdate,
+            url: format!("https://data.ris.ripe.net/rrc00/updates.{}.gz", i),
+            rough_size: 1024,
+            exact_size: 0
+        }).collect::<Vec<Item>>();
+        db.insert_items(&items).await.unwrap();
+        (db, items)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::db::models::Project;
 
     #[tokio::test]
+    #[ignore = "requires a Postgres database at postgres://localhost/mingwei"]
     async fn test_insert() {
-        let db = DbConnection::new("postgres://localhost/mingwei").await;
+        let db = DbConnection::new("postgres://localhost/mingwei").await.unwrap();
 
         let collectors = vec![
             Collector{
                 id: "rrc00".to_string(),
-                project: "ris".to_string(),
-                url: "1".to_string()
+                project: Project::RipeRis,
+                url: "1".to_string(),
+                meta: Default::default()
             },
             Collector{
                 id: "rrc01".to_string(),
-                project: "ris".to_string(),
-                url: "2".to_string()
+                project: Project::RipeRis,
+                url: "2".to_string(),
+                meta: Default::default()
             },
         ];
-        db.insert_collectors(&collectors).await;
+        db.insert_collectors(&collectors).await.unwrap();
 
         let items = vec![
             Item{
                 ts_start: chrono::Utc::now().naive_utc(),
                 ts_end: chrono::Utc::now().naive_utc(),
                 collector_id: "rrc00".to_string(),
-                data_type: "update".to_string(),
+                data_type: DataType::BgpUpdate,
                 url: "test".to_string(),
                 rough_size: 0,
                 exact_size: 1
@@ -245,18 +812,101 @@ mod tests {
                 ts_start: chrono::Utc::now().naive_utc(),
                 ts_end: chrono::Utc::now().naive_utc(),
                 collector_id: "rrc00".to_string(),
-                data_type: "update".to_string(),
+                data_type: DataType::BgpUpdate,
                 url: "test2".to_string(),
                 rough_size: 0,
                 exact_size: 2
             },
         ];
-        let inserted = db.insert_items(&items).await;
+        let inserted = db.insert_items(&items).await.unwrap();
         assert_eq!(inserted.len(), 2);
-        let inserted = db.insert_items(&items).await;
+        let inserted = db.insert_items(&items).await.unwrap();
         assert_eq!(inserted.len(), 0);
 
-        dbg!(db.get_urls_in_month("rrc00", "2022.08").await);
-        dbg!(db.count_records_in_month("rrc00", "2022.08").await);
+        dbg!(db.get_urls_in_month("rrc00", "2022.08").await.unwrap());
+        dbg!(db.count_records_in_month("rrc00", "2022.08").await.unwrap());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_sqlite() {
+        use chrono::NaiveDate;
+
+        let db = DbConnection::new("sqlite::memory:").await.unwrap();
+        let mut collector = test_utils::test_collector();
+        db.insert_collectors(&[collector.clone()]).await.unwrap();
+        // metadata of existing collectors is updated
+        collector.meta.country = Some("NL".to_string());
+        collector.meta.activated_on = NaiveDate::from_ymd_opt(1999, 10, 1);
+        collector.meta.latitude = Some(52.37);
+        db.insert_collectors(&[collector.clone()]).await.unwrap();
+        let collectors = db.get_collectors().await.unwrap();
+        assert_eq!(collectors.len(), 1);
+        assert_eq!(collectors[0].meta, collector.meta);
+
+        let ts = NaiveDateTime::parse_from_str("2022-08-01T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        let items = (0..3).map(|i| Item{
+            ts_start: ts + chrono::Duration::minutes(5 * i),
+            ts_end: ts + chrono::Duration::minutes(5 * i + 5),
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpUpdate,
+            url: format!("https://data.ris.ripe.net/rrc00/2022.08/updates.20220801.{:04}.gz", 5 * i),
+            rough_size: 0,
+            exact_size: 0
+        }).collect::<Vec<Item>>();
+        assert_eq!(db.insert_items(&items).await.unwrap().len(), 3);
+        assert_eq!(db.insert_items(&items).await.unwrap().len(), 0);
+
+        assert_eq!(db.count_records_in_month("rrc00", "2022.08").await.unwrap(), 3);
+        assert_eq!(db.get_urls_in_month("rrc00", "2022.08").await.unwrap().len(), 3);
+        assert_eq!(db.get_timestamps("rrc00", DataType::BgpUpdate, ts, ts + chrono::Duration::hours(1)).await.unwrap().len(), 3);
+
+        db.update_exact_sizes(&[(items[0].url.clone(), 100)]).await.unwrap();
+        // a single 404 is retried, consecutive ones flag the item as missing
+        db.record_not_found(&[items[1].url.clone()]).await.unwrap();
+        assert_eq!(db.get_unverified_items("rrc00", 10).await.unwrap().len(), 2);
+        for _ in 0..MISSING_AFTER_NOT_FOUND {
+            db.record_not_found(&[items[1].url.clone()]).await.unwrap();
+        }
+        let unverified = db.get_unverified_items("rrc00", 10).await.unwrap();
+        assert_eq!(unverified.len(), 1);
+        assert_eq!(unverified[0].url, items[2].url);
+
+        let filter = ItemFilter{
+            project: Some(Project::RipeRis),
+            collector_ids: vec!["rrc00".to_string()],
+            data_type: Some(DataType::BgpUpdate),
+            ..Default::default()
+        };
+        // missing items are left out
+        let found = db.search_items(&filter, 1, 2).await.unwrap();
+        assert_eq!(found.iter().map(|i| i.url.clone()).collect::<Vec<String>>(), vec![items[0].url.clone(), items[2].url.clone()]);
+        assert!(db.search_items(&filter, 2, 2).await.unwrap().is_empty());
+
+        let latest = db.get_latest().await.unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest[0].item_url, items[2].url);
+        assert!(latest[0].active);
+
+        // files filled in later do not replace a newer latest file
+        let older = Item{
+            ts_start: ts - chrono::Duration::minutes(5),
+            url: "https://data.ris.ripe.net/rrc00/2022.07/updates.20220731.2355.gz".to_string(),
+            ..items[0].clone()
+        };
+        assert_eq!(db.insert_items(&[older]).await.unwrap().len(), 1);
+        assert_eq!(db.get_latest().await.unwrap()[0].item_url, items[2].url);
+
+        let listing = "https://data.ris.ripe.net/rrc00/2022.08/";
+        assert_eq!(db.get_listing_validators(listing).await.unwrap(), None);
+        let validators = Validators{ etag: Some("\"v1\"".to_string()), last_modified: None };
+        db.save_listing_validators(listing, &validators).await.unwrap();
+        let validators = Validators{ etag: Some("\"v2\"".to_string()), last_modified: None };
+        db.save_listing_validators(listing, &validators).await.unwrap();
+        assert_eq!(db.get_listing_validators(listing).await.unwrap(), Some(validators));
+
+        // rows with unknown data types are rejected when read
+        db.pool.execute("UPDATE items SET data_type='bgp' WHERE url LIKE '%20220731.2355%'").await.unwrap();
+        assert!(db.search_items(&ItemFilter::default(), 1, 10).await.is_err());
     }
-}
\ No newline at end of file
+}
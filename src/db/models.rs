@@ -1,35 +1,245 @@
-use serde::{Deserialize, Serialize, Serializer};
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr};
+use std::str::FromStr;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde::ser::SerializeStruct;
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{Any, Decode, Encode};
+use sqlx::any::{AnyArgumentBuffer, AnyTypeInfo, AnyValueRef};
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Collector {
     pub id: String,
-    pub project: String,
+    pub project: Project,
     pub url: String,
+    #[serde(flatten)]
+    pub meta: CollectorMeta,
 }
 
+/// Optional descriptive metadata of a collector, and whether it is still active.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CollectorMeta {
+    /// ISO 3166-1 alpha-2 country code of the collector's location
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub country: Option<String>,
+    /// city or site of the collector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub location: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latitude: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub longitude: Option<f64>,
+    /// IXP or organization hosting the collector
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub org: Option<String>,
+    /// address family of the collector's peerings: `ipv4`, `ipv6` or `dual`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_family: Option<String>,
+    /// date the collector started publishing data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activated_on: Option<NaiveDate>,
+    /// date the collector stopped publishing data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decommissioned_on: Option<NaiveDate>,
+    /// expected minutes between RIB dumps, overriding the project's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rib_interval_minutes: Option<i64>,
+    /// expected minutes between updates files, overriding the project's default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub update_interval_minutes: Option<i64>,
+    /// retired collectors are inactive, they are neither scraped for new files nor checked for
+    /// gaps
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+fn default_active() -> bool {
+    true
+}
+
+impl Default for CollectorMeta {
+    fn default() -> Self {
+        CollectorMeta {
+            country: None,
+            location: None,
+            latitude: None,
+            longitude: None,
+            org: None,
+            ip_family: None,
+            activated_on: None,
+            decommissioned_on: None,
+            rib_interval_minutes: None,
+            update_interval_minutes: None,
+            active: true,
+        }
+    }
+}
+
+impl Collector {
+    /// Get the configured interval between files of a data type, if any.
+    pub fn expected_interval(&self, data_type: DataType) -> Option<chrono::Duration> {
+        let minutes = match data_type {
+            DataType::BgpTableDump => self.meta.rib_interval_minutes,
+            DataType::BgpUpdate => self.meta.update_interval_minutes,
+        };
+        minutes.map(chrono::Duration::minutes)
+    }
+
+    /// Clamp a time range to the period the collector published data in, returning `None` if
+    /// they do not overlap.
+    pub fn active_range(&self, from: NaiveDateTime, to: NaiveDateTime) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        let from = match self.meta.activated_on {
+            Some(d) => from.max(d.and_hms_opt(0, 0, 0).unwrap()),
+            None => from
+        };
+        let to = match self.meta.decommissioned_on {
+            Some(d) => to.min(d.and_hms_opt(0, 0, 0).unwrap()),
+            None => to
+        };
+        (from < to).then_some((from, to))
+    }
+}
 
-#[derive(Debug, Clone, Copy)]
+
+/// Type of an MRT file, stored as `rib` or `update`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum DataType {
+    #[serde(rename = "update")]
     BgpUpdate,
+    #[serde(rename = "rib")]
     BgpTableDump,
 }
 
 impl DataType {
-    pub fn to_string(self) -> String {
+    pub const ALL: [DataType; 2] = [DataType::BgpTableDump, DataType::BgpUpdate];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DataType::BgpUpdate => "update",
+            DataType::BgpTableDump => "rib",
+        }
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for DataType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "update" => Ok(DataType::BgpUpdate),
+            "rib" => Ok(DataType::BgpTableDump),
+            _ => Err(format!("unknown data type {}, expecting 'rib' or 'update'", s))
+        }
+    }
+}
+
+/// Data collection project of a collector, stored by name. Projects registered in addition to
+/// the ones shipped with this crate, e.g. local mirrors, are `Other`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
+pub enum Project {
+    RouteViews,
+    RipeRis,
+    Pch,
+    Isolario,
+    Other(String),
+}
+
+impl Project {
+    pub fn as_str(&self) -> &str {
         match self {
-            DataType::BgpUpdate => {"update".to_string()}
-            DataType::BgpTableDump => {"rib".to_string()}
+            Project::RouteViews => "routeviews",
+            Project::RipeRis => "riperis",
+            Project::Pch => "pch",
+            Project::Isolario => "isolario",
+            Project::Other(name) => name.as_str(),
         }
     }
 }
 
-#[derive(Debug, Eq, PartialEq, sqlx::FromRow)]
+impl From<&str> for Project {
+    fn from(name: &str) -> Self {
+        match name {
+            "routeviews" => Project::RouteViews,
+            "riperis" => Project::RipeRis,
+            "pch" => Project::Pch,
+            "isolario" => Project::Isolario,
+            other => Project::Other(other.to_string()),
+        }
+    }
+}
+
+impl FromStr for Project {
+    type Err = Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Project::from(s))
+    }
+}
+
+impl Display for Project {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl Serialize for Project {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Project {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        Ok(Project::from(String::deserialize(deserializer)?.as_str()))
+    }
+}
+
+/// Both enums are stored as text, which the `Any` driver decodes as strings.
+macro_rules! impl_text_column {
+    ($ty:ty) => {
+        impl sqlx::Type<Any> for $ty {
+            fn type_info() -> AnyTypeInfo {
+                <String as sqlx::Type<Any>>::type_info()
+            }
+
+            fn compatible(ty: &AnyTypeInfo) -> bool {
+                <String as sqlx::Type<Any>>::compatible(ty)
+            }
+        }
+
+        impl<'q> Encode<'q, Any> for $ty {
+            fn encode_by_ref(&self, buf: &mut AnyArgumentBuffer<'q>) -> IsNull {
+                <String as Encode<'q, Any>>::encode(self.as_str().to_string(), buf)
+            }
+        }
+
+        impl<'r> Decode<'r, Any> for $ty {
+            fn decode(value: AnyValueRef<'r>) -> Result<Self, BoxDynError> {
+                let text = <String as Decode<'r, Any>>::decode(value)?;
+                Ok(<$ty>::from_str(text.as_str())?)
+            }
+        }
+    };
+}
+
+impl_text_column!(DataType);
+impl_text_column!(Project);
+
+#[derive(Debug, Clone, Eq, PartialEq, sqlx::FromRow)]
 pub struct Item {
     pub ts_start: chrono::NaiveDateTime,
     pub ts_end: chrono::NaiveDateTime,
     pub collector_id: String,
-    pub data_type: String,
+    pub data_type: DataType,
     pub url: String,
     pub rough_size: i64,
     pub exact_size: i64,
@@ -47,4 +257,164 @@ impl Serialize for Item {
         state.serialize_field("exact_size", &self.exact_size)?;
         state.end()
     }
-}
\ No newline at end of file
+}
+
+impl<'de> Deserialize<'de> for Item {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+        /// Item as serialized, with timestamps as strings.
+        #[derive(Deserialize)]
+        struct RawItem {
+            ts_start: String,
+            ts_end: String,
+            collector_id: String,
+            data_type: DataType,
+            url: String,
+            rough_size: i64,
+            exact_size: i64,
+        }
+
+        let raw = RawItem::deserialize(deserializer)?;
+        let parse_ts = |ts: &str| NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S")
+            .map_err(|e| serde::de::Error::custom(format!("invalid timestamp {}: {}", ts, e)));
+        Ok(Item {
+            ts_start: parse_ts(raw.ts_start.as_str())?,
+            ts_end: parse_ts(raw.ts_end.as_str())?,
+            collector_id: raw.collector_id,
+            data_type: raw.data_type,
+            url: raw.url,
+            rough_size: raw.rough_size,
+            exact_size: raw.exact_size,
+        })
+    }
+}
+
+/// Filters for searching [Item]s, unset filters match all items.
+#[derive(Debug, Default, Clone)]
+pub struct ItemFilter {
+    /// match items ending at or after this time
+    pub ts_start: Option<NaiveDateTime>,
+    /// match items starting at or before this time
+    pub ts_end: Option<NaiveDateTime>,
+    /// match items from collectors of this project
+    pub project: Option<Project>,
+    /// match items from any of these collectors, empty matches all collectors
+    pub collector_ids: Vec<String>,
+    /// match items of this data type
+    pub data_type: Option<DataType>,
+    /// match items of collectors peering with this AS at the time, from the first indexed RIB
+    /// dump listing the peer up to the next indexed dump not listing it
+    pub peer_asn: Option<u32>,
+    /// match items of collectors peering with this IP address at the time, from the first
+    /// indexed RIB dump listing the peer up to the next indexed dump not listing it
+    pub peer_ip: Option<IpAddr>,
+}
+
+/// A BGP peer of a collector, as listed in the `PEER_INDEX_TABLE` of its RIB dumps.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Peer {
+    pub collector_id: String,
+    pub asn: u32,
+    pub ip: IpAddr,
+    pub bgp_id: Ipv4Addr,
+    /// start time of the earliest indexed RIB dump listing the peer
+    pub first_seen: NaiveDateTime,
+    /// start time of the latest indexed RIB dump listing the peer
+    pub last_seen: NaiveDateTime,
+}
+
+/// Filters for listing [Peer]s, unset filters match all peers.
+#[derive(Debug, Default, Clone)]
+pub struct PeerFilter {
+    /// match peers of any of these collectors, empty matches all collectors
+    pub collector_ids: Vec<String>,
+    pub asn: Option<u32>,
+    pub ip: Option<IpAddr>,
+}
+
+/// The latest item of a collector for one data type.
+#[derive(Debug, Eq, PartialEq)]
+pub struct Latest {
+    pub timestamp: NaiveDateTime,
+    pub collector_id: String,
+    pub data_type: DataType,
+    pub rough_size: i64,
+    pub exact_size: i64,
+    pub project: Project,
+    pub collector_url: String,
+    pub item_url: String,
+    /// whether the collector is still active, delays of inactive collectors are expected
+    pub active: bool,
+}
+
+impl Serialize for Latest {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        // delay is calculated at serialization time so that it reflects the current time
+        let delay = (chrono::Utc::now().naive_utc() - self.timestamp).num_seconds();
+        let mut state = serializer.serialize_struct("Latest", 10)?;
+        state.serialize_field("timestamp", self.timestamp.format("%Y-%m-%dT%H:%M:%S").to_string().as_str())?;
+        state.serialize_field("delay", &delay)?;
+        state.serialize_field("collector_id", self.collector_id.as_str())?;
+        state.serialize_field("data_type", self.data_type.as_str())?;
+        state.serialize_field("item_url", self.item_url.as_str())?;
+        state.serialize_field("rough_size", &self.rough_size)?;
+        state.serialize_field("exact_size", &self.exact_size)?;
+        state.serialize_field("project", self.project.as_str())?;
+        state.serialize_field("collector_url", self.collector_url.as_str())?;
+        state.serialize_field("active", &self.active)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collector_meta() {
+        let collector: Collector = serde_json::from_str(r#"{
+            "id": "rrc02",
+            "project": "riperis",
+            "url": "https://data.ris.ripe.net/rrc02",
+            "activated_on": "2001-03-01",
+            "decommissioned_on": "2008-11-01",
+            "update_interval_minutes": 5
+        }"#).unwrap();
+        assert!(collector.meta.active);
+        assert_eq!(collector.project, Project::RipeRis);
+        assert_eq!(collector.expected_interval(DataType::BgpUpdate), Some(chrono::Duration::minutes(5)));
+        assert_eq!(collector.expected_interval(DataType::BgpTableDump), None);
+
+        let ts = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(collector.active_range(ts("2008-10-01 00:00"), ts("2009-01-01 00:00")),
+                   Some((ts("2008-10-01 00:00"), ts("2008-11-01 00:00"))));
+        assert_eq!(collector.active_range(ts("2010-01-01 00:00"), ts("2010-02-01 00:00")), None);
+    }
+
+    #[test]
+    fn test_item_json() {
+        let item = Item {
+            ts_start: NaiveDateTime::parse_from_str("2022-10-01T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap(),
+            ts_end: NaiveDateTime::parse_from_str("2022-10-01T00:15:00", "%Y-%m-%dT%H:%M:%S").unwrap(),
+            collector_id: "route-views2".to_string(),
+            data_type: DataType::BgpUpdate,
+            url: "http://archive.routeviews.org/bgpdata/2022.10/UPDATES/updates.20221001.0000.bz2".to_string(),
+            rough_size: 1024,
+            exact_size: 1000,
+        };
+        let json = serde_json::to_string(&item).unwrap();
+        assert!(json.contains(r#""ts_end":"2022-10-01T00:15:00""#));
+        assert_eq!(serde_json::from_str::<Item>(json.as_str()).unwrap(), item);
+        assert!(serde_json::from_str::<Item>(json.replace("2022-10-01T00:15:00", "yesterday").as_str()).is_err());
+        assert!(serde_json::from_str::<Item>(json.replace(r#""update""#, r#""bgp""#).as_str()).is_err());
+    }
+
+    #[test]
+    fn test_project() {
+        assert_eq!(Project::from("routeviews"), Project::RouteViews);
+        assert_eq!(Project::from("rv-mirror"), Project::Other("rv-mirror".to_string()));
+        assert_eq!(serde_json::to_string(&Project::from("rv-mirror")).unwrap(), r#""rv-mirror""#);
+        assert_eq!(serde_json::from_str::<Project>(r#""pch""#).unwrap(), Project::Pch);
+        assert_eq!("rib".parse::<DataType>().unwrap(), DataType::BgpTableDump);
+        assert!("bgp".parse::<DataType>().is_err());
+    }
+}
@@ -0,0 +1,10 @@
+//! New file notification sinks
+use async_trait::async_trait;
+use crate::db::models::Item;
+use crate::errors::ScrapeError;
+
+/// A sink notified with the items newly inserted by the updater, e.g. a Kafka topic or a webhook.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, items: &[Item]) -> Result<(), ScrapeError>;
+}
@@ -0,0 +1,19 @@
+//! Embedded SQLite backend.
+//!
+//! The schema lives in `migrations-sqlite/`, mirroring the Postgres migrations in `migrations/`,
+//! and is applied on connection.
+
+use std::str::FromStr;
+
+use sqlx::ConnectOptions;
+use sqlx::sqlite::SqliteConnectOptions;
+use crate::errors::ScrapeError;
+
+pub(crate) fn url_to_options(db_url: &str) -> Result<SqliteConnectOptions, ScrapeError> {
+    let mut opts = SqliteConnectOptions::from_str(db_url)
+        .map_err(|e| ScrapeError::ConfigError(format!("invalid database URL {}: {}", db_url, e)))?
+        .create_if_missing(true)
+        .foreign_keys(true);
+    opts.disable_statement_logging();
+    Ok(opts)
+}
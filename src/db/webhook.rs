@@ -0,0 +1,175 @@
+//! HTTP webhook notifier
+//!
+//! New items are POSTed as a JSON array to the configured URL, in batches. When a secret is set,
+//! the body is signed with HMAC-SHA256 and the hex digest is sent in the
+//! `X-Broker-Signature: sha256=<digest>` header, so receivers can check the sender.
+
+use std::time::Duration;
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use sha2::Sha256;
+use crate::db::models::Item;
+use crate::db::notifier::Notifier;
+use crate::errors::ScrapeError;
+
+pub const SIGNATURE_HEADER: &str = "X-Broker-Signature";
+
+pub struct WebhookNotifier {
+    client: reqwest::Client,
+    url: String,
+    secret: Option<String>,
+    batch_size: usize,
+    max_retries: u32,
+    retry_delay: Duration,
+}
+
+/// Sign a payload with HMAC-SHA256, returning the hex digest.
+pub fn sign_payload(secret: &str, payload: &[u8]) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(payload);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+impl WebhookNotifier {
+    /// Create a notifier posting up to 100 items per request, retrying failed requests 3 times.
+    pub fn new(url: &str) -> WebhookNotifier {
+        info!("initializing webhook notifier with url: {}", url);
+        WebhookNotifier{
+            client: reqwest::Client::new(),
+            url: url.to_string(),
+            secret: None,
+            batch_size: 100,
+            max_retries: 3,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+
+    /// Sign request bodies with the given secret.
+    pub fn with_secret(mut self, secret: &str) -> Self {
+        self.secret = Some(secret.to_string());
+        self
+    }
+
+    /// Maximum number of items sent in one request.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Number of retries after a failed request, and the delay before the first retry, doubled
+    /// after each attempt.
+    pub fn with_retries(mut self, max_retries: u32, retry_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    async fn post(&self, payload: &str) -> Result<(), ScrapeError> {
+        let mut request = self.client.post(self.url.as_str())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(payload.to_string());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign_payload(secret, payload.as_bytes())));
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        if status.is_success() {
+            Ok(())
+        } else {
+            Err(ScrapeError::NotificationError(format!("webhook {} responded with {}", self.url, status)))
+        }
+    }
+
+    async fn post_with_retries(&self, payload: &str) -> Result<(), ScrapeError> {
+        let mut delay = self.retry_delay;
+        let mut attempt = 0;
+        loop {
+            match self.post(payload).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.max_retries => {
+                    warn!("{}, retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, items: &[Item]) -> Result<(), ScrapeError> {
+        for batch in items.chunks(self.batch_size) {
+            let payload = serde_json::to_string(batch).unwrap();
+            self.post_with_retries(payload.as_str()).await?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::models::DataType;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::test_server::{serve, Response};
+
+    /// Accept POST requests, failing the first `failures` of them with 503, and record the
+    /// signature headers and bodies of the accepted ones.
+    async fn start_server(failures: usize) -> (String, Arc<Mutex<Vec<(String, String)>>>) {
+        let received = Arc::new(Mutex::new(vec![]));
+        let requests = received.clone();
+        let count = AtomicUsize::new(0);
+        let url = serve(move |request| {
+            if count.fetch_add(1, Ordering::SeqCst) < failures {
+                return Response::new("503 Service Unavailable")
+            }
+            let signature = request.header(SIGNATURE_HEADER.to_lowercase().as_str()).unwrap_or_default().to_string();
+            requests.lock().unwrap().push((signature, request.body));
+            Response::ok("")
+        }).await;
+        (format!("{}/hook", url), received)
+    }
+
+    fn items(n: usize) -> Vec<Item> {
+        (0..n).map(|i| Item{
+            ts_start: chrono::NaiveDateTime::parse_from_str("2022-08-01T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap(),
+            ts_end: chrono::NaiveDateTime::parse_from_str("2022-08-01T00:05:00", "%Y-%m-%dT%H:%M:%S").unwrap(),
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpUpdate,
+            url: format!("https://data.ris.ripe.net/rrc00/2022.08/updates.20220801.{:04}.gz", i),
+            rough_size: 0,
+            exact_size: 0
+        }).collect()
+    }
+
+    #[tokio::test]
+    async fn test_webhook_batches_and_signature() {
+        let (url, received) = start_server(1).await;
+        let notifier = WebhookNotifier::new(url.as_str())
+            .with_secret("secret")
+            .with_batch_size(2)
+            .with_retries(1, Duration::from_millis(10));
+        notifier.notify(&items(3)).await.unwrap();
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (signature, body) = &received[0];
+        let batch: Vec<Item> = serde_json::from_str(body).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert_eq!(signature, &format!("sha256={}", sign_payload("secret", body.as_bytes())));
+    }
+
+    #[tokio::test]
+    async fn test_webhook_gives_up() {
+        let (url, received) = start_server(usize::MAX).await;
+        let notifier = WebhookNotifier::new(url.as_str())
+            .with_retries(2, Duration::from_millis(10));
+        assert!(notifier.notify(&items(1)).await.is_err());
+        assert!(received.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,165 @@
+//! Discover collectors from the root pages of the data sources.
+//!
+//! Projects with a `discovery_url` in the collectors config have their root page crawled by
+//! their scraper, e.g. the RouteViews archive root or the RIPE RIS data root. Collectors found
+//! upstream but not configured are new, configured collectors not found upstream are stale.
+
+use std::collections::HashSet;
+use log::{error, info};
+use serde::Serialize;
+use crate::config::Config;
+use crate::db::DbConnection;
+use crate::db::models::{Collector, Project};
+use crate::errors::ScrapeError;
+use crate::scrapers::{CrawlMode, ScraperRegistry};
+
+/// Result of discovering the collectors of one project.
+#[derive(Debug, Clone, Serialize)]
+pub struct Discovery {
+    pub project: Project,
+    /// collectors found upstream that are not in the config
+    pub new: Vec<Collector>,
+    /// IDs of configured collectors not found upstream
+    pub stale: Vec<String>,
+    /// why the project's collectors could not be discovered, with no new or stale collectors
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Discover the collectors of every project with a `discovery_url` and compare them to the
+/// configured collectors. A project whose root page cannot be crawled gets an error, without
+/// stopping the discovery of the other projects.
+pub async fn discover_collectors(config: &Config, registry: &ScraperRegistry) -> Result<Vec<Discovery>, ScrapeError> {
+    let configured = config.to_collectors();
+    let mut discoveries = vec![];
+    for (project, url) in config.discovery_urls() {
+        let scraper = registry.build(project.as_str(), CrawlMode::Latest)
+            .ok_or_else(|| ScrapeError::ConfigError(format!("no scraper registered for project {}", project)))?;
+        info!("discovering collectors of {} from {}", project, url);
+        let found = match scraper.discover(&project, url).await {
+            // most likely a changed or unreachable root page, reporting every collector as
+            // stale would not help
+            Ok(found) if found.is_empty() => Err(ScrapeError::ParseError(format!("no collectors of {} found at {}", project, url))),
+            res => res,
+        };
+        let found = match found {
+            Ok(found) => found,
+            Err(e) => {
+                error!("discovering collectors of {} failed: {}", project, e);
+                discoveries.push(Discovery { project, new: vec![], stale: vec![], error: Some(e.to_string()) });
+                continue
+            }
+        };
+
+        let found_ids = found.iter().map(|c| c.id.as_str()).collect::<HashSet<&str>>();
+        let configured = configured.iter().filter(|c| c.project == project).collect::<Vec<&Collector>>();
+        let stale = configured.iter()
+            .filter(|c| !found_ids.contains(c.id.as_str()))
+            .map(|c| c.id.clone())
+            .collect::<Vec<String>>();
+        let new = found.iter()
+            .filter(|c| !configured.iter().any(|configured| configured.id == c.id))
+            .cloned()
+            .collect::<Vec<Collector>>();
+        info!("discovered {} collectors of {}, {} new, {} stale", found.len(), project, new.len(), stale.len());
+        discoveries.push(Discovery { project, new, stale, error: None });
+    }
+    Ok(discoveries)
+}
+
+/// Register the new collectors of the discoveries that are not in the database yet, returning
+/// the registered collectors. Collectors already in the database keep their metadata.
+pub async fn register_new_collectors(db: &DbConnection, discoveries: &[Discovery]) -> Result<Vec<Collector>, ScrapeError> {
+    let known = db.get_collectors().await?.into_iter().map(|c| c.id).collect::<HashSet<String>>();
+    let to_register = discoveries.iter()
+        .flat_map(|d| d.new.iter())
+        .filter(|c| !known.contains(&c.id))
+        .cloned()
+        .collect::<Vec<Collector>>();
+    for c in &to_register {
+        info!("registering discovered collector {} of {} at {}", c.id, c.project, c.url);
+    }
+    db.insert_collectors(&to_register).await?;
+    Ok(to_register)
+}
+
+/// Collectors registered by discovery, i.e. collectors in the database of projects with a
+/// `discovery_url` that are not in the config.
+pub async fn registered_collectors(config: &Config, db: &DbConnection) -> Result<Vec<Collector>, ScrapeError> {
+    let projects = config.discovery_urls().into_iter().map(|(project, _)| project).collect::<Vec<Project>>();
+    let configured = config.to_collectors().into_iter().map(|c| c.id).collect::<HashSet<String>>();
+    Ok(db.get_collectors().await?.into_iter()
+        .filter(|c| projects.contains(&c.project) && !configured.contains(&c.id))
+        .collect())
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::scrapers::fixtures;
+
+    #[tokio::test]
+    async fn test_discover_collectors() {
+        let routeviews = fixtures::serve("routeviews", vec![]).await;
+        let riperis = fixtures::serve("riperis", vec![]).await;
+        let config: Config = serde_json::from_str(format!(r#"{{"projects": [
+            {{"name": "routeviews", "discovery_url": "{}/", "collectors": [
+                {{"id": "route-views2", "url": "{}/bgpdata"}},
+                {{"id": "route-views.linx", "url": "{}/route-views.linx/bgpdata", "country": "GB"}},
+                {{"id": "route-views.retired", "url": "{}/route-views.retired/bgpdata"}}
+            ]}},
+            {{"name": "riperis", "discovery_url": "{}", "collectors": [
+                {{"id": "rrc00", "url": "{}/rrc00"}}
+            ]}},
+            {{"name": "pch", "collectors": [
+                {{"id": "route-collector.ams.pch.net", "url": "https://www.pch.net/resources/Raw_Routing_Data/route-collector.ams.pch.net"}}
+            ]}}
+        ]}}"#, routeviews.url, routeviews.url, routeviews.url, routeviews.url, riperis.url, riperis.url).as_str()).unwrap();
+        let registry = ScraperRegistry::default();
+
+        let discoveries = discover_collectors(&config, &registry).await.unwrap();
+        assert_eq!(discoveries.len(), 2);
+        let new_ids = |d: &Discovery| d.new.iter().map(|c| c.id.clone()).collect::<Vec<String>>();
+        assert_eq!(discoveries[0].project, Project::RouteViews);
+        assert_eq!(new_ids(&discoveries[0]), vec!["route-views.sydney", "route-views3"]);
+        assert_eq!(discoveries[0].new[1].url, format!("{}/route-views3/bgpdata", routeviews.url));
+        assert_eq!(discoveries[0].stale, vec!["route-views.retired"]);
+        assert_eq!(discoveries[1].project, Project::RipeRis);
+        assert_eq!(new_ids(&discoveries[1]), vec!["rrc01", "rrc27"]);
+        assert_eq!(discoveries[1].new[1].url, format!("{}/rrc27", riperis.url));
+        assert!(discoveries[1].stale.is_empty());
+
+        let db = DbConnection::new("sqlite::memory:").await.unwrap();
+        db.insert_collectors(&config.to_collectors()).await.unwrap();
+        // rrc01 is already in the database with its own metadata
+        let mut rrc01 = discoveries[1].new[0].clone();
+        rrc01.meta.country = Some("GB".to_string());
+        db.insert_collectors(&[rrc01.clone()]).await.unwrap();
+
+        let registered = register_new_collectors(&db, &discoveries).await.unwrap();
+        assert_eq!(registered.iter().map(|c| c.id.as_str()).collect::<Vec<&str>>(), vec!["route-views.sydney", "route-views3", "rrc27"]);
+        let collectors = db.get_collectors().await.unwrap();
+        assert_eq!(collectors.iter().find(|c| c.id == "rrc01").unwrap().meta, rrc01.meta);
+        assert_eq!(collectors.iter().find(|c| c.id == "route-views.linx").unwrap().meta.country.as_deref(), Some("GB"));
+        assert!(register_new_collectors(&db, &discoveries).await.unwrap().is_empty());
+
+        let mut ids = registered_collectors(&config, &db).await.unwrap().into_iter().map(|c| c.id).collect::<Vec<String>>();
+        ids.sort();
+        assert_eq!(ids, vec!["route-views.sydney", "route-views3", "rrc01", "rrc27"]);
+
+        // an empty or unreachable root page fails its project instead of reporting every
+        // collector as stale, and the other projects are still discovered
+        let config: Config = serde_json::from_str(format!(r#"{{"projects": [
+            {{"name": "routeviews", "discovery_url": "http://127.0.0.1:1/", "collectors": []}},
+            {{"name": "riperis", "discovery_url": "{}/missing", "collectors": [{{"id": "rrc00", "url": "{}/rrc00"}}]}},
+            {{"name": "riperis", "discovery_url": "{}", "collectors": []}}
+        ]}}"#, riperis.url, riperis.url, riperis.url).as_str()).unwrap();
+        let discoveries = discover_collectors(&config, &registry).await.unwrap();
+        assert_eq!(discoveries.len(), 3);
+        assert!(discoveries[0].error.is_some());
+        assert!(discoveries[1].error.as_deref().unwrap().contains("no collectors of riperis"));
+        assert!(discoveries[1].new.is_empty() && discoveries[1].stale.is_empty());
+        assert!(discoveries[2].error.is_none());
+        assert_eq!(new_ids(&discoveries[2]), vec!["rrc01", "rrc27"]);
+    }
+}
@@ -0,0 +1,138 @@
+//! Dump and load the index as newline-delimited JSON.
+//!
+//! A dump has one record per line, either `{"collector": {...}}` or `{"item": {...}}`, with all
+//! collectors written before the items. Loading inserts collectors as they are read and items in
+//! batches through [DbConnection::insert_items], so loading a dump twice adds nothing the second
+//! time.
+
+use std::io::{BufRead, Write};
+use log::info;
+use serde::{Deserialize, Serialize};
+use crate::db::DbConnection;
+use crate::db::models::{Collector, Item, ItemFilter};
+use crate::errors::ScrapeError;
+
+/// Number of items inserted per batch when loading.
+const LOAD_BATCH_SIZE: usize = 10_000;
+
+/// A line of a dump.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Record {
+    Collector(Collector),
+    Item(Item),
+}
+
+/// Numbers of records dumped or loaded.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct DumpStats {
+    pub collectors: usize,
+    pub items: usize,
+    /// items not in the database before loading, always zero for dumps
+    pub new_items: usize,
+}
+
+fn write_record<W: Write>(writer: &mut W, record: &Record) -> Result<(), ScrapeError> {
+    serde_json::to_writer(&mut *writer, record)
+        .map_err(|e| ScrapeError::IoError(format!("writing record failed: {}", e)))?;
+    writer.write_all(b"\n")?;
+    Ok(())
+}
+
+/// Dump the collectors and items matching the filter. Items are read a month at a time.
+pub async fn dump<W: Write>(db: &DbConnection, filter: &ItemFilter, mut writer: W) -> Result<DumpStats, ScrapeError> {
+    let mut stats = DumpStats::default();
+    let collectors = db.get_collectors().await?.into_iter()
+        .filter(|c| filter.project.as_ref().map(|p| p == &c.project).unwrap_or(true))
+        .filter(|c| filter.collector_ids.is_empty() || filter.collector_ids.contains(&c.id));
+    for collector in collectors {
+        write_record(&mut writer, &Record::Collector(collector))?;
+        stats.collectors += 1;
+    }
+
+    for (month_start, month_end) in db.get_item_months(filter).await? {
+        let items = db.get_items_starting_in(filter, month_start, month_end).await?;
+        info!("dumping {} items of {}", items.len(), month_start.format("%Y-%m"));
+        stats.items += items.len();
+        for item in items {
+            write_record(&mut writer, &Record::Item(item))?;
+        }
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+async fn load_items(db: &DbConnection, items: &mut Vec<Item>, stats: &mut DumpStats) -> Result<(), ScrapeError> {
+    if items.is_empty() {
+        return Ok(())
+    }
+    stats.new_items += db.insert_items(items).await?.len();
+    stats.items += items.len();
+    info!("loaded {} items, {} new", stats.items, stats.new_items);
+    items.clear();
+    Ok(())
+}
+
+/// Load a dump. Existing items are kept as they are, existing collectors are updated.
+pub async fn load<R: BufRead>(db: &DbConnection, reader: R) -> Result<DumpStats, ScrapeError> {
+    let mut stats = DumpStats::default();
+    let mut items = vec![];
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue
+        }
+        let record = serde_json::from_str::<Record>(line.as_str())
+            .map_err(|e| ScrapeError::ParseError(format!("invalid record on line {}: {}", i + 1, e)))?;
+        match record {
+            Record::Collector(collector) => {
+                // items reference their collector, insert the ones read so far first
+                load_items(db, &mut items, &mut stats).await?;
+                db.insert_collectors(&[collector]).await?;
+                stats.collectors += 1;
+            }
+            Record::Item(item) => {
+                items.push(item);
+                if items.len() >= LOAD_BATCH_SIZE {
+                    load_items(db, &mut items, &mut stats).await?;
+                }
+            }
+        }
+    }
+    load_items(db, &mut items, &mut stats).await?;
+    Ok(stats)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use crate::db::test_utils::seeded_db;
+
+    #[tokio::test]
+    async fn test_dump_load() {
+        let (db, items) = seeded_db().await;
+
+        let mut buffer = vec![];
+        let stats = dump(&db, &ItemFilter::default(), &mut buffer).await.unwrap();
+        assert_eq!((stats.collectors, stats.items), (1, 4));
+        let text = String::from_utf8(buffer.clone()).unwrap();
+        assert!(text.lines().next().unwrap().starts_with(r#"{"collector":{"id":"rrc00""#));
+        assert_eq!(text.lines().count(), 5);
+
+        let target = DbConnection::new("sqlite::memory:").await.unwrap();
+        let stats = load(&target, buffer.as_slice()).await.unwrap();
+        assert_eq!(stats, DumpStats { collectors: 1, items: 4, new_items: 4 });
+        // loading again is a no-op
+        let stats = load(&target, buffer.as_slice()).await.unwrap();
+        assert_eq!(stats.new_items, 0);
+        let loaded = target.search_items(&ItemFilter::default(), 1, 10).await.unwrap();
+        assert_eq!(loaded, items);
+
+        // filters apply to the dumped items
+        let mut buffer = vec![];
+        let filter = ItemFilter { ts_start: Some(items[3].ts_start), ..Default::default() };
+        assert_eq!(dump(&db, &filter, &mut buffer).await.unwrap().items, 2);
+
+        assert!(load(&target, "{\"item\": {}}\n".as_bytes()).await.is_err());
+    }
+}
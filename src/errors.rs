@@ -4,6 +4,13 @@ use std::error::Error;
 #[derive(Debug)]
 pub enum ScrapeError {
     NetworkError(String),
+    IoError(String),
+    /// Unexpected content of a listing page, file name or user input
+    ParseError(String),
+    DatabaseError(String),
+    NotificationError(String),
+    /// Invalid configuration, e.g. collectors config file or database URL
+    ConfigError(String),
 }
 
 impl Display for ScrapeError {
@@ -12,6 +19,21 @@ impl Display for ScrapeError {
             ScrapeError::NetworkError(err) => {
                 write!(f, "Scraping network error: {}", err)
             }
+            ScrapeError::IoError(err) => {
+                write!(f, "Scraping IO error: {}", err)
+            }
+            ScrapeError::ParseError(err) => {
+                write!(f, "Parsing error: {}", err)
+            }
+            ScrapeError::DatabaseError(err) => {
+                write!(f, "Database error: {}", err)
+            }
+            ScrapeError::NotificationError(err) => {
+                write!(f, "Notification error: {}", err)
+            }
+            ScrapeError::ConfigError(err) => {
+                write!(f, "Configuration error: {}", err)
+            }
         }
     }
 }
@@ -24,3 +46,49 @@ impl From<reqwest::Error> for ScrapeError {
     }
 }
 
+
+impl From<std::io::Error> for ScrapeError {
+    fn from(err: std::io::Error) -> Self {
+        ScrapeError::IoError(err.to_string())
+    }
+}
+
+impl From<sqlx::Error> for ScrapeError {
+    fn from(err: sqlx::Error) -> Self {
+        ScrapeError::DatabaseError(err.to_string())
+    }
+}
+
+impl From<chrono::ParseError> for ScrapeError {
+    fn from(err: chrono::ParseError) -> Self {
+        ScrapeError::ParseError(err.to_string())
+    }
+}
+
+/// Blocking tasks are used to parse listings off the async runtime.
+impl From<tokio::task::JoinError> for ScrapeError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        ScrapeError::ParseError(format!("parsing task failed: {}", err))
+    }
+}
+
+#[cfg(feature = "kafka")]
+impl From<rdkafka::error::KafkaError> for ScrapeError {
+    fn from(err: rdkafka::error::KafkaError) -> Self {
+        ScrapeError::NotificationError(err.to_string())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<parquet::errors::ParquetError> for ScrapeError {
+    fn from(err: parquet::errors::ParquetError) -> Self {
+        ScrapeError::IoError(err.to_string())
+    }
+}
+
+#[cfg(feature = "parquet")]
+impl From<arrow_schema::ArrowError> for ScrapeError {
+    fn from(err: arrow_schema::ArrowError) -> Self {
+        ScrapeError::IoError(err.to_string())
+    }
+}
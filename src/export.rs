@@ -0,0 +1,148 @@
+//! Export the index to Apache Parquet files.
+//!
+//! Items are written to Hive-style partitions by project, year and month, e.g.
+//! `project=riperis/year=2022/month=10/items.parquet`, which DuckDB and Spark read back as
+//! partition columns. Timestamps are stored as UTC timestamps in microseconds.
+
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use arrow_array::{ArrayRef, Int64Array, RecordBatch, StringArray, TimestampMicrosecondArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use chrono::{Datelike, NaiveDateTime};
+use log::info;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use crate::db::DbConnection;
+use crate::db::models::{Item, ItemFilter, Project};
+use crate::errors::ScrapeError;
+
+/// Number of rows per record batch and row group.
+const BATCH_SIZE: usize = 65_536;
+
+/// An exported partition file.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Partition {
+    pub project: Project,
+    pub year: i32,
+    pub month: u32,
+    pub path: PathBuf,
+    pub rows: usize,
+}
+
+/// Arrow schema of exported items.
+pub fn items_schema() -> Arc<Schema> {
+    let timestamp = DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into()));
+    Arc::new(Schema::new(vec![
+        Field::new("ts_start", timestamp.clone(), false),
+        Field::new("ts_end", timestamp, false),
+        Field::new("collector_id", DataType::Utf8, false),
+        Field::new("data_type", DataType::Utf8, false),
+        Field::new("url", DataType::Utf8, false),
+        Field::new("rough_size", DataType::Int64, false),
+        Field::new("exact_size", DataType::Int64, false),
+    ]))
+}
+
+fn items_to_batch(schema: Arc<Schema>, items: &[Item]) -> Result<RecordBatch, ScrapeError> {
+    let micros = |ts: &NaiveDateTime| ts.and_utc().timestamp_micros();
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(TimestampMicrosecondArray::from_iter_values(items.iter().map(|i| micros(&i.ts_start))).with_timezone("UTC")),
+        Arc::new(TimestampMicrosecondArray::from_iter_values(items.iter().map(|i| micros(&i.ts_end))).with_timezone("UTC")),
+        Arc::new(StringArray::from_iter_values(items.iter().map(|i| i.collector_id.as_str()))),
+        Arc::new(StringArray::from_iter_values(items.iter().map(|i| i.data_type.as_str()))),
+        Arc::new(StringArray::from_iter_values(items.iter().map(|i| i.url.as_str()))),
+        Arc::new(Int64Array::from_iter_values(items.iter().map(|i| i.rough_size))),
+        Arc::new(Int64Array::from_iter_values(items.iter().map(|i| i.exact_size))),
+    ];
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Write items to a Snappy-compressed Parquet file. The file is written next to the target and
+/// renamed when complete, so readers never see partial files.
+pub fn write_items(path: &Path, items: &[Item]) -> Result<(), ScrapeError> {
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("parquet.tmp");
+    let schema = items_schema();
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .set_max_row_group_size(BATCH_SIZE)
+        .build();
+    let mut writer = ArrowWriter::try_new(File::create(&tmp_path)?, schema.clone(), Some(props))?;
+    for chunk in items.chunks(BATCH_SIZE) {
+        writer.write(&items_to_batch(schema.clone(), chunk)?)?;
+    }
+    writer.close()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+/// Path of a partition file within the output directory.
+pub fn partition_path(out_dir: &Path, project: &str, year: i32, month: u32) -> PathBuf {
+    out_dir
+        .join(format!("project={}", project))
+        .join(format!("year={}", year))
+        .join(format!("month={:02}", month))
+        .join("items.parquet")
+}
+
+/// Export the items matching the filter to partitions in `out_dir`, replacing existing
+/// partition files. Partitions without items are not written.
+pub async fn export_items(db: &DbConnection, filter: &ItemFilter, out_dir: &Path) -> Result<Vec<Partition>, ScrapeError> {
+    let mut projects = db.get_collectors().await?.into_iter()
+        .map(|c| c.project)
+        .filter(|p| filter.project.as_ref().map(|f| f == p).unwrap_or(true))
+        .collect::<Vec<Project>>();
+    projects.sort();
+    projects.dedup();
+
+    let mut partitions = vec![];
+    for (month_start, month_end) in db.get_item_months(filter).await? {
+        for project in &projects {
+            let project_filter = ItemFilter { project: Some(project.clone()), ..filter.clone() };
+            let items = db.get_items_starting_in(&project_filter, month_start, month_end).await?;
+            if items.is_empty() {
+                continue
+            }
+            let path = partition_path(out_dir, project.as_str(), month_start.year(), month_start.month());
+            info!("exporting {} items to {}", items.len(), path.display());
+            let rows = items.len();
+            let write_path = path.clone();
+            tokio::task::spawn_blocking(move || write_items(&write_path, &items)).await
+                .map_err(|e| ScrapeError::IoError(format!("writing task failed: {}", e)))??;
+            partitions.push(Partition { project: project.clone(), year: month_start.year(), month: month_start.month(), path, rows });
+        }
+    }
+    Ok(partitions)
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use crate::db::test_utils::seeded_db;
+
+    #[tokio::test]
+    async fn test_export() {
+        let (db, items) = seeded_db().await;
+
+        let out_dir = std::env::temp_dir().join(format!("bgpkit-broker-export-{}", std::process::id()));
+        let partitions = export_items(&db, &ItemFilter::default(), &out_dir).await.unwrap();
+        assert_eq!(partitions.iter().map(|p| (p.month, p.rows)).collect::<Vec<_>>(), vec![(9, 2), (10, 2)]);
+        assert!(partitions[1].path.ends_with("project=riperis/year=2022/month=10/items.parquet"));
+
+        let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&partitions[1].path).unwrap()).unwrap();
+        assert_eq!(reader.schema().as_ref(), items_schema().as_ref());
+        let batches = reader.build().unwrap().collect::<Result<Vec<RecordBatch>, _>>().unwrap();
+        let ts_start = batches[0].column(0).as_any().downcast_ref::<TimestampMicrosecondArray>().unwrap();
+        assert_eq!(ts_start.value(0), items[2].ts_start.and_utc().timestamp_micros());
+
+        // only October when filtering by time
+        let filter = ItemFilter { ts_start: Some(items[3].ts_start), ..Default::default() };
+        assert_eq!(export_items(&db, &filter, &out_dir).await.unwrap().len(), 1);
+        std::fs::remove_dir_all(&out_dir).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+//! Shared HTTP fetch layer for requests to the upstream archives.
+//!
+//! All scrapers and verification requests go through one [Fetcher], which limits the number of
+//! concurrent requests and the request rate per host, and retries connection errors, `429` and
+//! `5xx` responses with exponential backoff.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use log::{debug, warn};
+use reqwest::{Method, Response, StatusCode};
+use reqwest::header::{HeaderMap, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, RANGE};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{Semaphore, SemaphorePermit};
+use crate::errors::ScrapeError;
+use crate::metrics::metrics;
+
+static FETCHER: OnceLock<Fetcher> = OnceLock::new();
+
+/// Fetch layer settings, the `fetch` section of the collectors config.
+///
+/// ```json
+/// "fetch": {
+///   "max_retries": 3,
+///   "retry_delay_ms": 1000,
+///   "default_limits": {"max_concurrency": 10},
+///   "hosts": {
+///     "archive.routeviews.org": {"max_concurrency": 4, "requests_per_second": 5.0}
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FetchConfig {
+    /// retries after the first failed attempt
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// delay before the first retry, doubled after each attempt
+    #[serde(default = "default_retry_delay_ms")]
+    pub retry_delay_ms: u64,
+    /// timeout of a single request, including downloading the body
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u64,
+    /// limits of hosts without an entry in `hosts`
+    #[serde(default)]
+    pub default_limits: HostLimits,
+    /// limits by host name
+    #[serde(default)]
+    pub hosts: HashMap<String, HostLimits>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HostLimits {
+    /// maximum number of requests in flight
+    #[serde(default = "default_max_concurrency")]
+    pub max_concurrency: usize,
+    /// maximum number of requests started per second, unlimited if not set
+    #[serde(default)]
+    pub requests_per_second: Option<f64>,
+}
+
+fn default_max_retries() -> u32 { 3 }
+fn default_retry_delay_ms() -> u64 { 1000 }
+fn default_timeout_secs() -> u64 { 300 }
+fn default_max_concurrency() -> usize { 10 }
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            max_retries: default_max_retries(),
+            retry_delay_ms: default_retry_delay_ms(),
+            timeout_secs: default_timeout_secs(),
+            default_limits: HostLimits::default(),
+            hosts: HashMap::new(),
+        }
+    }
+}
+
+impl Default for HostLimits {
+    fn default() -> Self {
+        HostLimits { max_concurrency: default_max_concurrency(), requests_per_second: None }
+    }
+}
+
+/// HTTP cache validators of a listing page, sent back in conditional requests.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+impl Validators {
+    fn from_headers(headers: &HeaderMap) -> Validators {
+        let get = |name| headers.get(name).and_then(|v| v.to_str().ok()).map(|v| v.to_string());
+        Validators { etag: get(ETAG), last_modified: get(LAST_MODIFIED) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.etag.is_none() && self.last_modified.is_none()
+    }
+}
+
+/// Result of a conditional listing request.
+#[derive(Debug)]
+pub enum Listing {
+    /// the page did not change since the validators were saved
+    NotModified,
+    Modified { body: String, validators: Validators },
+}
+
+/// Outcome of one request attempt.
+enum Attempt<T> {
+    Done(T),
+    Retry(ScrapeError),
+    Fail(ScrapeError),
+}
+
+struct HostLimiter {
+    semaphore: Semaphore,
+    /// minimum time between two request starts
+    interval: Option<Duration>,
+    next_slot: Mutex<Instant>,
+}
+
+impl HostLimiter {
+    fn new(limits: &HostLimits) -> HostLimiter {
+        HostLimiter {
+            semaphore: Semaphore::new(limits.max_concurrency.max(1)),
+            interval: limits.requests_per_second
+                .filter(|rps| *rps > 0.0)
+                .map(|rps| Duration::from_secs_f64(1.0 / rps)),
+            next_slot: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for a free request slot of the host, the request may start once this returns.
+    async fn acquire(&self) -> SemaphorePermit<'_> {
+        let permit = self.semaphore.acquire().await.expect("host semaphore is never closed");
+        if let Some(interval) = self.interval {
+            let wait = {
+                let mut next_slot = self.next_slot.lock().unwrap();
+                let now = Instant::now();
+                let slot = (*next_slot).max(now);
+                *next_slot = slot + interval;
+                slot - now
+            };
+            tokio::time::sleep(wait).await;
+        }
+        permit
+    }
+}
+
+pub struct Fetcher {
+    client: reqwest::Client,
+    config: FetchConfig,
+    limiters: Mutex<HashMap<String, Arc<HostLimiter>>>,
+}
+
+/// Set up the shared fetcher, must be called before the first request.
+pub fn configure(config: FetchConfig) -> Result<(), ScrapeError> {
+    FETCHER.set(Fetcher::new(config))
+        .map_err(|_| ScrapeError::ConfigError("fetch layer is already configured".to_string()))
+}
+
+/// The shared fetcher, with default settings unless [configure]d.
+pub fn fetcher() -> &'static Fetcher {
+    FETCHER.get_or_init(|| Fetcher::new(FetchConfig::default()))
+}
+
+fn host_of(url: &str) -> String {
+    url::Url::parse(url).ok()
+        .and_then(|u| u.host_str().map(|h| h.to_string()))
+        .unwrap_or_default()
+}
+
+/// Count a failed request attempt of a host in the metrics.
+fn count_error(host: &str, error: &str) {
+    metrics().http_errors.with_label_values(&[host, error]).inc();
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+impl Fetcher {
+    pub fn new(config: FetchConfig) -> Fetcher {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+            .expect("reqwest client with default TLS settings");
+        Fetcher { client, config, limiters: Mutex::new(HashMap::new()) }
+    }
+
+    fn limiter(&self, url: &str) -> Arc<HostLimiter> {
+        let host = host_of(url);
+        let mut limiters = self.limiters.lock().unwrap();
+        limiters.entry(host.clone()).or_insert_with(|| {
+            let limits = self.config.hosts.get(host.as_str()).unwrap_or(&self.config.default_limits);
+            Arc::new(HostLimiter::new(limits))
+        }).clone()
+    }
+
+    async fn with_retries<T, F, Fut>(&self, url: &str, mut attempt: F) -> Result<T, ScrapeError>
+        where F: FnMut() -> Fut, Fut: Future<Output = Attempt<T>>
+    {
+        let limiter = self.limiter(url);
+        let mut delay = Duration::from_millis(self.config.retry_delay_ms);
+        let mut retries = 0;
+        loop {
+            let result = {
+                let _permit = limiter.acquire().await;
+                attempt().await
+            };
+            match result {
+                Attempt::Done(value) => return Ok(value),
+                Attempt::Retry(e) if retries < self.config.max_retries => {
+                    warn!("{}, retrying in {:?}", e, delay);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    retries += 1;
+                }
+                Attempt::Retry(e) | Attempt::Fail(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Download a listing page. A missing page (404) is treated as an empty listing.
+    pub async fn get_text(&self, url: &str) -> Result<String, ScrapeError> {
+        match self.get_listing(url, None).await? {
+            Listing::Modified { body, .. } => Ok(body),
+            Listing::NotModified => Ok(String::new()),
+        }
+    }
+
+    /// Download a listing page unless it is unchanged according to the given validators from a
+    /// previous download. A missing page (404) is treated as an empty listing.
+    pub async fn get_listing(&self, url: &str, validators: Option<&Validators>) -> Result<Listing, ScrapeError> {
+        let host = host_of(url);
+        let host = host.as_str();
+        self.with_retries(url, || async {
+            let mut request = self.client.get(url);
+            if let Some(validators) = validators {
+                if let Some(etag) = &validators.etag {
+                    request = request.header(IF_NONE_MATCH, etag.as_str());
+                }
+                if let Some(last_modified) = &validators.last_modified {
+                    request = request.header(IF_MODIFIED_SINCE, last_modified.as_str());
+                }
+            }
+            let resp = match request.send().await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    count_error(host, "connection");
+                    return Attempt::Retry(e.into())
+                }
+            };
+            let status = resp.status();
+            if status == StatusCode::NOT_MODIFIED {
+                metrics().listings_fetched.with_label_values(&[host, "not_modified"]).inc();
+                Attempt::Done(Listing::NotModified)
+            } else if status.is_success() {
+                let validators = Validators::from_headers(resp.headers());
+                match resp.text().await {
+                    Ok(body) => {
+                        metrics().listings_fetched.with_label_values(&[host, "modified"]).inc();
+                        metrics().bytes_downloaded.with_label_values(&[host]).inc_by(body.len() as u64);
+                        Attempt::Done(Listing::Modified { body, validators })
+                    }
+                    Err(e) => {
+                        count_error(host, "connection");
+                        Attempt::Retry(e.into())
+                    }
+                }
+            } else if status == StatusCode::NOT_FOUND {
+                debug!("{} not found, treated as empty", url);
+                Attempt::Done(Listing::Modified { body: String::new(), validators: Validators::default() })
+            } else if is_retryable(status) {
+                count_error(host, status.as_str());
+                Attempt::Retry(ScrapeError::NetworkError(format!("{} responded with {}", url, status)))
+            } else {
+                count_error(host, status.as_str());
+                Attempt::Fail(ScrapeError::NetworkError(format!("{} responded with {}", url, status)))
+            }
+        }).await
+    }
+
+    /// Download the first `len` bytes of a file with a Range request, or `None` if the file is
+    /// missing (404). Servers ignoring the range send the whole file, of which only the first
+    /// `len` bytes are read.
+    pub async fn get_range(&self, url: &str, len: usize) -> Result<Option<Vec<u8>>, ScrapeError> {
+        let host = host_of(url);
+        let host = host.as_str();
+        self.with_retries(url, || async {
+            let request = self.client.get(url).header(RANGE, format!("bytes=0-{}", len.saturating_sub(1)));
+            let mut resp = match request.send().await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    count_error(host, "connection");
+                    return Attempt::Retry(e.into())
+                }
+            };
+            let status = resp.status();
+            if status.is_success() {
+                let mut body = Vec::with_capacity(len);
+                while body.len() < len {
+                    match resp.chunk().await {
+                        Ok(Some(chunk)) => body.extend_from_slice(&chunk),
+                        Ok(None) => break,
+                        Err(e) => {
+                            count_error(host, "connection");
+                            return Attempt::Retry(e.into())
+                        }
+                    }
+                }
+                body.truncate(len);
+                metrics().bytes_downloaded.with_label_values(&[host]).inc_by(body.len() as u64);
+                Attempt::Done(Some(body))
+            } else if status == StatusCode::NOT_FOUND {
+                Attempt::Done(None)
+            } else if is_retryable(status) {
+                count_error(host, status.as_str());
+                Attempt::Retry(ScrapeError::NetworkError(format!("{} responded with {}", url, status)))
+            } else {
+                count_error(host, status.as_str());
+                Attempt::Fail(ScrapeError::NetworkError(format!("{} responded with {}", url, status)))
+            }
+        }).await
+    }
+
+    /// Send a request without a body, e.g. `HEAD`. Responses with client error statuses are
+    /// returned as they are, server errors only once the retries are exhausted.
+    pub async fn request(&self, method: Method, url: &str) -> Result<Response, ScrapeError> {
+        let host = host_of(url);
+        let host = host.as_str();
+        self.with_retries(url, || async {
+            match self.client.request(method.clone(), url).send().await {
+                Ok(resp) if is_retryable(resp.status()) => {
+                    count_error(host, resp.status().as_str());
+                    Attempt::Retry(ScrapeError::NetworkError(format!("{} responded with {}", url, resp.status())))
+                }
+                Ok(resp) => Attempt::Done(resp),
+                Err(e) => {
+                    count_error(host, "connection");
+                    Attempt::Retry(e.into())
+                }
+            }
+        }).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use crate::test_server::{serve, Response};
+
+    /// Serve `hello` on every request, except the first `failures` requests which get a 503.
+    async fn start_server(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let requests = count.clone();
+        let url = serve(move |_| match requests.fetch_add(1, Ordering::SeqCst) < failures {
+            true => Response::new("503 Service Unavailable"),
+            false => Response::ok("hello"),
+        }).await;
+        (url, count)
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let (url, count) = start_server(2).await;
+        let fetcher = Fetcher::new(FetchConfig { max_retries: 2, retry_delay_ms: 10, ..Default::default() });
+        assert_eq!(fetcher.get_text(url.as_str()).await.unwrap(), "hello");
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        let (url, count) = start_server(usize::MAX).await;
+        assert!(fetcher.get_text(url.as_str()).await.is_err());
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    /// Serve a page with an `ETag`, answering 304 to requests with a matching `If-None-Match`.
+    async fn start_etag_server() -> String {
+        serve(|request| match request.header("if-none-match") {
+            Some("\"v1\"") => Response::new("304 Not Modified").header("ETag", "\"v1\""),
+            _ => Response::ok("hello").header("ETag", "\"v1\""),
+        }).await
+    }
+
+    #[tokio::test]
+    async fn test_conditional_listing() {
+        let url = start_etag_server().await;
+        let fetcher = Fetcher::new(FetchConfig::default());
+        let validators = match fetcher.get_listing(url.as_str(), None).await.unwrap() {
+            Listing::Modified { body, validators } => {
+                assert_eq!(body, "hello");
+                validators
+            }
+            Listing::NotModified => panic!("first request must download the page"),
+        };
+        assert_eq!(validators.etag.as_deref(), Some("\"v1\""));
+        assert!(matches!(fetcher.get_listing(url.as_str(), Some(&validators)).await.unwrap(), Listing::NotModified));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let (url, _) = start_server(0).await;
+        let mut config = FetchConfig::default();
+        config.hosts.insert("127.0.0.1".to_string(), HostLimits { max_concurrency: 10, requests_per_second: Some(20.0) });
+        let fetcher = Fetcher::new(config);
+
+        let start = Instant::now();
+        let results = futures::future::join_all((0..5).map(|_| fetcher.get_text(url.as_str()))).await;
+        assert!(results.iter().all(|r| r.is_ok()));
+        // five requests at 20 per second start over at least 200ms
+        assert!(start.elapsed() >= Duration::from_millis(200));
+    }
+}
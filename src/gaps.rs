@@ -0,0 +1,186 @@
+//! Detect missing MRT files by comparing indexed timestamps against the expected cadence of each
+//! collector and data type.
+
+use chrono::{Duration, NaiveDateTime};
+use serde::{Serialize, Serializer};
+use serde::ser::SerializeStruct;
+use crate::db::DbConnection;
+use crate::db::models::{Collector, DataType};
+use crate::errors::ScrapeError;
+use crate::scrapers::{CrawlMode, ScraperRegistry};
+
+/// A period in which files of a collector and data type are missing.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Gap {
+    pub collector_id: String,
+    pub data_type: DataType,
+    /// expected time of the first missing file
+    pub gap_start: NaiveDateTime,
+    /// time of the next available file, or the end of the checked period
+    pub gap_end: NaiveDateTime,
+    /// estimated number of missing files
+    pub missing_files: i64,
+}
+
+impl Serialize for Gap {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+        let mut state = serializer.serialize_struct("Gap", 5)?;
+        state.serialize_field("collector_id", self.collector_id.as_str())?;
+        state.serialize_field("data_type", self.data_type.as_str())?;
+        state.serialize_field("gap_start", self.gap_start.format("%Y-%m-%dT%H:%M:%S").to_string().as_str())?;
+        state.serialize_field("gap_end", self.gap_end.format("%Y-%m-%dT%H:%M:%S").to_string().as_str())?;
+        state.serialize_field("missing_files", &self.missing_files)?;
+        state.end()
+    }
+}
+
+/// Find gaps in sorted timestamps of files published every `interval` within `[from, to)`.
+///
+/// Consecutive files more than one and a half intervals apart are considered to have missing
+/// files in between, which tolerates files published slightly off the expected schedule.
+pub fn find_gaps(timestamps: &[NaiveDateTime], interval: Duration, from: NaiveDateTime, to: NaiveDateTime) -> Vec<(NaiveDateTime, NaiveDateTime, i64)> {
+    let tolerance = interval + interval / 2;
+    let mut gaps = vec![];
+
+    // the time of the previously seen file, starting from one interval before the period so
+    // that a file exactly at `from` is not a gap
+    let mut prev = from - interval;
+    for ts in timestamps.iter().chain(std::iter::once(&to)) {
+        if *ts < from || *ts > to {
+            continue
+        }
+        let diff = *ts - prev;
+        if diff > tolerance {
+            let missing = diff.num_seconds() / interval.num_seconds() - 1;
+            gaps.push((prev + interval, *ts, missing.max(1)));
+        }
+        prev = *ts;
+    }
+    gaps
+}
+
+/// Find gaps of all data types of the given collectors within `[from, to)`.
+///
+/// Only the period each collector was active in is checked, and inactive collectors are skipped.
+/// The expected intervals configured for a collector take precedence over its project's, and
+/// collectors without a regular publishing cadence are skipped.
+pub async fn find_collector_gaps(collectors: &[Collector], registry: &ScraperRegistry, db: &DbConnection, from: NaiveDateTime, to: NaiveDateTime) -> Result<Vec<Gap>, ScrapeError> {
+    let mut gaps = vec![];
+    for collector in collectors {
+        if !collector.meta.active {
+            continue
+        }
+        let (from, to) = match collector.active_range(from, to) {
+            Some(range) => range,
+            None => continue
+        };
+        let scraper = match registry.build(collector.project.as_str(), CrawlMode::Latest) {
+            Some(s) => s,
+            None => continue
+        };
+        for data_type in DataType::ALL {
+            let interval = match collector.expected_interval(data_type).or_else(|| scraper.expected_interval(data_type)) {
+                Some(i) => i,
+                None => continue
+            };
+            let timestamps = db.get_timestamps(collector.id.as_str(), data_type, from, to).await?;
+            gaps.extend(find_gaps(&timestamps, interval, from, to).into_iter().map(|(gap_start, gap_end, missing_files)| {
+                Gap {
+                    collector_id: collector.id.clone(),
+                    data_type,
+                    gap_start,
+                    gap_end,
+                    missing_files,
+                }
+            }));
+        }
+    }
+    Ok(gaps)
+}
+
+/// Format gaps as CSV with a header line.
+pub fn gaps_to_csv(gaps: &[Gap]) -> String {
+    let mut lines = vec!["collector_id,data_type,gap_start,gap_end,missing_files".to_string()];
+    for gap in gaps {
+        lines.push(format!("{},{},{},{},{}",
+                           gap.collector_id, gap.data_type,
+                           gap.gap_start.format("%Y-%m-%dT%H:%M:%S"),
+                           gap.gap_end.format("%Y-%m-%dT%H:%M:%S"),
+                           gap.missing_files));
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let interval = Duration::minutes(15);
+        let from = ts("2022-10-01 00:00");
+        let to = ts("2022-10-01 02:00");
+
+        // complete sequence
+        let timestamps: Vec<NaiveDateTime> = (0..8).map(|i| from + interval * i).collect();
+        assert!(find_gaps(&timestamps, interval, from, to).is_empty());
+
+        // slightly late files are not gaps
+        let timestamps = vec![ts("2022-10-01 00:00"), ts("2022-10-01 00:16"), ts("2022-10-01 00:30"),
+                              ts("2022-10-01 00:45"), ts("2022-10-01 01:00"), ts("2022-10-01 01:15"),
+                              ts("2022-10-01 01:30"), ts("2022-10-01 01:45")];
+        assert!(find_gaps(&timestamps, interval, from, to).is_empty());
+
+        // missing 00:00, 00:45-01:15 and 01:45
+        let timestamps = vec![ts("2022-10-01 00:15"), ts("2022-10-01 00:30"), ts("2022-10-01 01:30")];
+        assert_eq!(find_gaps(&timestamps, interval, from, to), vec![
+            (ts("2022-10-01 00:00"), ts("2022-10-01 00:15"), 1),
+            (ts("2022-10-01 00:45"), ts("2022-10-01 01:30"), 3),
+            (ts("2022-10-01 01:45"), ts("2022-10-01 02:00"), 1),
+        ]);
+
+        // nothing indexed
+        assert_eq!(find_gaps(&[], interval, from, to), vec![(from, to, 8)]);
+    }
+
+    #[test]
+    fn test_gaps_to_csv() {
+        let gaps = vec![Gap{
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpUpdate,
+            gap_start: ts("2022-10-01 00:05"),
+            gap_end: ts("2022-10-01 00:20"),
+            missing_files: 3,
+        }];
+        assert_eq!(gaps_to_csv(&gaps), "collector_id,data_type,gap_start,gap_end,missing_files\nrrc00,update,2022-10-01T00:05:00,2022-10-01T00:20:00,3");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_find_collector_gaps() {
+        use crate::db::MISSING_AFTER_NOT_FOUND;
+        use crate::db::test_utils::{seeded_db, test_collector};
+
+        let (db, items) = seeded_db().await;
+        let registry = ScraperRegistry::default();
+        let (from, to) = (ts("2022-09-30 23:50"), ts("2022-10-01 00:10"));
+        assert!(find_collector_gaps(&[test_collector()], &registry, &db, from, to).await.unwrap().is_empty());
+
+        // files flagged as missing after repeated 404s are gaps
+        for _ in 0..MISSING_AFTER_NOT_FOUND {
+            db.record_not_found(&[items[1].url.clone()]).await.unwrap();
+        }
+        let gaps = find_collector_gaps(&[test_collector()], &registry, &db, from, to).await.unwrap();
+        assert_eq!(gaps, vec![Gap{
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpUpdate,
+            gap_start: items[1].ts_start,
+            gap_end: items[2].ts_start,
+            missing_files: 1,
+        }]);
+    }
+}
@@ -4,3 +4,15 @@ pub mod scrapers;
 pub mod errors;
 pub mod config;
 pub mod db;
+pub mod verify;
+pub mod peers;
+pub mod discovery;
+pub mod gaps;
+pub mod fetch;
+pub mod metrics;
+pub mod dump;
+pub mod updater;
+#[cfg(feature = "parquet")]
+pub mod export;
+#[cfg(test)]
+pub(crate) mod test_server;
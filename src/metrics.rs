@@ -0,0 +1,149 @@
+//! Prometheus metrics of the updater.
+//!
+//! Metrics are collected in a registry of their own and either pushed to a Pushgateway at the
+//! end of a run with [push], or rendered in the text exposition format with [render].
+
+use std::sync::OnceLock;
+use chrono::NaiveDateTime;
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, GaugeVec, Opts, Registry, TextEncoder};
+use crate::db::models::{DataType, Item, Latest};
+use crate::errors::ScrapeError;
+
+static METRICS: OnceLock<Metrics> = OnceLock::new();
+
+pub struct Metrics {
+    registry: Registry,
+    /// listing pages downloaded, by host and whether the page changed
+    pub listings_fetched: IntCounterVec,
+    /// bytes of listing pages downloaded, by host
+    pub bytes_downloaded: IntCounterVec,
+    /// failed HTTP requests, by host and status code (or `connection`)
+    pub http_errors: IntCounterVec,
+    /// items parsed from listings, by collector and data type
+    pub items_parsed: IntCounterVec,
+    /// items newly inserted into the database, by collector and data type
+    pub items_inserted: IntCounterVec,
+    /// duration of database inserts
+    pub db_insert_seconds: HistogramVec,
+    /// start time of the latest item in the database, by collector and data type
+    pub latest_item_timestamp: GaugeVec,
+    /// collectors whose scraping failed, by collector
+    pub collector_failures: IntCounterVec,
+}
+
+fn counter(registry: &Registry, name: &str, help: &str, labels: &[&str]) -> IntCounterVec {
+    let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+    registry.register(Box::new(counter.clone())).unwrap();
+    counter
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("bgpkit_broker".to_string()), None).unwrap();
+        let db_insert_seconds = HistogramVec::new(
+            HistogramOpts::new("db_insert_seconds", "Duration of database inserts in seconds"), &[]
+        ).unwrap();
+        registry.register(Box::new(db_insert_seconds.clone())).unwrap();
+        let latest_item_timestamp = GaugeVec::new(
+            Opts::new("latest_item_timestamp_seconds", "Start time of the latest item in unix seconds"),
+            &["collector", "data_type"]
+        ).unwrap();
+        registry.register(Box::new(latest_item_timestamp.clone())).unwrap();
+
+        Metrics {
+            listings_fetched: counter(&registry, "listings_fetched_total", "Listing pages downloaded", &["host", "status"]),
+            bytes_downloaded: counter(&registry, "bytes_downloaded_total", "Bytes of listing pages downloaded", &["host"]),
+            http_errors: counter(&registry, "http_errors_total", "Failed HTTP requests", &["host", "error"]),
+            items_parsed: counter(&registry, "items_parsed_total", "Items parsed from listings", &["collector", "data_type"]),
+            items_inserted: counter(&registry, "items_inserted_total", "Items newly inserted into the database", &["collector", "data_type"]),
+            collector_failures: counter(&registry, "collector_failures_total", "Failed collector scrapes", &["collector"]),
+            db_insert_seconds,
+            latest_item_timestamp,
+            registry,
+        }
+    }
+
+    /// Move the latest item timestamps forward to the given items.
+    pub fn update_latest(&self, items: &[Item]) {
+        for item in items {
+            self.set_latest(item.collector_id.as_str(), item.data_type, item.ts_start);
+        }
+    }
+
+    /// Initialize the latest item timestamps from the database.
+    pub fn init_latest(&self, latest: &[Latest]) {
+        for l in latest {
+            self.set_latest(l.collector_id.as_str(), l.data_type, l.timestamp);
+        }
+    }
+
+    fn set_latest(&self, collector_id: &str, data_type: DataType, ts: NaiveDateTime) {
+        let gauge = self.latest_item_timestamp.with_label_values(&[collector_id, data_type.as_str()]);
+        let ts = ts.and_utc().timestamp() as f64;
+        if ts > gauge.get() {
+            gauge.set(ts);
+        }
+    }
+}
+
+/// Count parsed or inserted items of a collector by data type.
+pub fn count_items(counter: &IntCounterVec, collector_id: &str, items: &[Item]) {
+    for data_type in DataType::ALL {
+        let count = items.iter().filter(|i| i.data_type == data_type).count();
+        if count > 0 {
+            counter.with_label_values(&[collector_id, data_type.as_str()]).inc_by(count as u64);
+        }
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    METRICS.get_or_init(Metrics::new)
+}
+
+/// Render all metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut buffer = vec![];
+    TextEncoder::new().encode(&metrics().registry.gather(), &mut buffer).unwrap();
+    String::from_utf8(buffer).unwrap()
+}
+
+/// Push all metrics to a Pushgateway, replacing the metrics previously pushed for the job.
+pub async fn push(gateway_url: &str, job: &str) -> Result<(), ScrapeError> {
+    let url = format!("{}/metrics/job/{}", gateway_url.trim_end_matches('/'), job);
+    let resp = reqwest::Client::new().put(url.as_str())
+        .header(reqwest::header::CONTENT_TYPE, TextEncoder::new().format_type())
+        .body(render())
+        .send().await?;
+    if !resp.status().is_success() {
+        return Err(ScrapeError::NetworkError(format!("pushing metrics to {} failed with {}", url, resp.status())))
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let ts = NaiveDateTime::parse_from_str("2022-10-01T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        let item = Item{
+            ts_start: ts,
+            ts_end: ts,
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpTableDump,
+            url: "https://data.ris.ripe.net/rrc00/2022.10/bview.20221001.0000.gz".to_string(),
+            rough_size: 0,
+            exact_size: 0
+        };
+        let items = [item.clone()];
+        count_items(&metrics().items_inserted, "rrc00", &items);
+        metrics().update_latest(&items);
+        // older items do not move the timestamp back
+        metrics().update_latest(&[Item{ ts_start: ts - chrono::Duration::days(1), ..item }]);
+
+        let text = render();
+        assert!(text.contains(r#"bgpkit_broker_items_inserted_total{collector="rrc00",data_type="rib"} 1"#));
+        assert!(text.contains(r#"bgpkit_broker_latest_item_timestamp_seconds{collector="rrc00",data_type="rib"} 1664582400"#));
+    }
+}
@@ -0,0 +1,397 @@
+//! Index the BGP peers of collectors from the `PEER_INDEX_TABLE` of RIB dumps.
+//!
+//! `TABLE_DUMP_V2` RIB dumps (RFC 6396) start with a `PEER_INDEX_TABLE` record listing the peers
+//! of the collector. Only the beginning of each compressed dump is downloaded with a Range
+//! request and decompressed until the record is complete, growing the range if it is not. bzip2
+//! decompresses whole blocks of up to 900 kB only, so RouteViews dumps need larger ranges than
+//! the gzip dumps of RIPE RIS.
+
+use std::io::{self, ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use bzip2::read::BzDecoder;
+use flate2::read::GzDecoder;
+use futures::StreamExt;
+use log::{info, warn};
+use tokio::io::AsyncReadExt;
+use crate::db::DbConnection;
+use crate::db::models::{Item, Peer};
+use crate::errors::ScrapeError;
+use crate::fetch::{fetcher, Fetcher};
+
+/// Number of concurrent RIB dump downloads per collector.
+const INDEX_CONCURRENCY: usize = 4;
+
+/// Bytes of a dump downloaded first, enough for the peer tables of gzip dumps.
+const INITIAL_RANGE: usize = 64 * 1024;
+
+/// Bytes of a dump downloaded at most, enough for the first bzip2 block.
+const MAX_RANGE: usize = 4 * 1024 * 1024;
+
+const TABLE_DUMP_V2: u16 = 13;
+const PEER_INDEX_TABLE: u16 = 1;
+
+/// A peer listed in a `PEER_INDEX_TABLE`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PeerEntry {
+    pub asn: u32,
+    pub ip: IpAddr,
+    pub bgp_id: Ipv4Addr,
+}
+
+/// Outcome of indexing the peers of one RIB dump.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum PeerIndexStatus {
+    Peers(Vec<PeerEntry>),
+    /// the dump does not start with a `PEER_INDEX_TABLE`, e.g. a legacy `TABLE_DUMP` file
+    NoPeerTable,
+    /// the file returned 404
+    Missing,
+    /// the download failed or the table could not be read, try again later
+    Unknown,
+}
+
+/// Split the first `n` bytes off the front of `data`.
+fn take<'a>(data: &mut &'a [u8], n: usize) -> io::Result<&'a [u8]> {
+    if data.len() < n {
+        return Err(io::Error::new(ErrorKind::InvalidData, "peer index table ends within a field"))
+    }
+    let (head, tail) = data.split_at(n);
+    *data = tail;
+    Ok(head)
+}
+
+fn take_u16(data: &mut &[u8]) -> io::Result<u16> {
+    let bytes = take(data, 2)?;
+    Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn take_u32(data: &mut &[u8]) -> io::Result<u32> {
+    let bytes = take(data, 4)?;
+    Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+/// Parse the body of a `PEER_INDEX_TABLE` record.
+fn parse_peer_index_table(mut data: &[u8]) -> io::Result<Vec<PeerEntry>> {
+    let data = &mut data;
+    let _collector_bgp_id = take_u32(data)?;
+    let view_name_len = take_u16(data)? as usize;
+    take(data, view_name_len)?;
+    let peer_count = take_u16(data)?;
+    let mut peers = Vec::with_capacity(peer_count as usize);
+    for _ in 0..peer_count {
+        // bit 0 of the peer type is set for IPv6 addresses, bit 1 for 4-byte ASNs
+        let peer_type = take(data, 1)?[0];
+        let bgp_id = Ipv4Addr::from(take_u32(data)?);
+        let ip = match peer_type & 0x01 {
+            0 => IpAddr::V4(Ipv4Addr::from(take_u32(data)?)),
+            _ => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(take(data, 16)?);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+        };
+        let asn = match peer_type & 0x02 {
+            0 => take_u16(data)? as u32,
+            _ => take_u32(data)?,
+        };
+        peers.push(PeerEntry { asn, ip, bgp_id });
+    }
+    Ok(peers)
+}
+
+/// Read the peers from the first MRT record of a decompressed dump, or `None` if the first
+/// record is not a `PEER_INDEX_TABLE`. Fails with [ErrorKind::UnexpectedEof] if the dump ends
+/// within the record.
+pub fn read_peer_index_table<R: Read>(mut reader: R) -> io::Result<Option<Vec<PeerEntry>>> {
+    let mut header = [0u8; 12];
+    reader.read_exact(&mut header)?;
+    let record_type = u16::from_be_bytes([header[4], header[5]]);
+    let subtype = u16::from_be_bytes([header[6], header[7]]);
+    if record_type != TABLE_DUMP_V2 || subtype != PEER_INDEX_TABLE {
+        return Ok(None)
+    }
+    let length = u32::from_be_bytes([header[8], header[9], header[10], header[11]]) as usize;
+    let mut body = vec![0u8; length];
+    reader.read_exact(&mut body)?;
+    parse_peer_index_table(body.as_slice()).map(Some)
+}
+
+/// Decompress the beginning of a dump according to the extension of its URL.
+fn decoder<'a>(url: &str, data: &'a [u8]) -> Box<dyn Read + 'a> {
+    if url.ends_with(".gz") {
+        Box::new(GzDecoder::new(data))
+    } else if url.ends_with(".bz2") {
+        Box::new(BzDecoder::new(data))
+    } else {
+        Box::new(data)
+    }
+}
+
+/// Read the first `len` bytes of a dump, from the filesystem for `file://` URLs of local
+/// mirrors.
+async fn read_range(fetcher: &Fetcher, url: &str, len: usize) -> Result<Option<Vec<u8>>, ScrapeError> {
+    let path = match url.strip_prefix("file://") {
+        Some(path) => path,
+        None => return fetcher.get_range(url, len).await
+    };
+    let file = match tokio::fs::File::open(path).await {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+    let mut data = Vec::with_capacity(len);
+    file.take(len as u64).read_to_end(&mut data).await?;
+    Ok(Some(data))
+}
+
+/// Download the beginning of a RIB dump and read its peer index table.
+pub async fn index_rib(fetcher: &Fetcher, url: &str) -> PeerIndexStatus {
+    let mut len = INITIAL_RANGE;
+    loop {
+        let data = match read_range(fetcher, url, len).await {
+            Ok(Some(data)) => data,
+            Ok(None) => return PeerIndexStatus::Missing,
+            Err(e) => {
+                warn!("failed to download the beginning of {}: {}", url, e);
+                return PeerIndexStatus::Unknown
+            }
+        };
+        match read_peer_index_table(decoder(url, data.as_slice())) {
+            Ok(Some(peers)) => return PeerIndexStatus::Peers(peers),
+            Ok(None) => return PeerIndexStatus::NoPeerTable,
+            // the table continues past the downloaded range
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof && data.len() == len && len < MAX_RANGE => {
+                len *= 4;
+            }
+            Err(e) => {
+                warn!("failed to read the peer index table of {}: {}", url, e);
+                return PeerIndexStatus::Unknown
+            }
+        }
+    }
+}
+
+/// Index the peers of up to `limit` most recent RIB dumps of a collector that are not indexed
+/// yet, and save the peers and missing files to the database.
+pub async fn index_collector_peers(collector_id: &str, db: &DbConnection, limit: i64) -> Result<(), ScrapeError> {
+    let ribs = db.get_unindexed_ribs(collector_id, limit).await?;
+    info!("indexing peers of {} RIB dumps for {}", ribs.len(), collector_id);
+    let statuses = futures::stream::iter(&ribs)
+        .map(|item| index_rib(fetcher(), item.url.as_str()))
+        .buffered(INDEX_CONCURRENCY)
+        .collect::<Vec<PeerIndexStatus>>().await;
+
+    let mut indexed = 0;
+    let mut missing = vec![];
+    for (item, status) in ribs.iter().zip(statuses) {
+        match status {
+            PeerIndexStatus::Peers(entries) => {
+                db.save_rib_peers(item.url.as_str(), &to_peers(item, &entries)).await?;
+                indexed += 1;
+            }
+            PeerIndexStatus::NoPeerTable => {
+                db.save_rib_peers(item.url.as_str(), &[]).await?;
+                indexed += 1;
+            }
+            PeerIndexStatus::Missing => missing.push(item.url.clone()),
+            PeerIndexStatus::Unknown => {}
+        }
+    }
+    db.record_not_found(&missing).await?;
+    info!("indexing peers of {} RIB dumps for {}... {} indexed, {} missing", ribs.len(), collector_id, indexed, missing.len());
+    Ok(())
+}
+
+/// Peers of a collector seen in one RIB dump.
+fn to_peers(item: &Item, entries: &[PeerEntry]) -> Vec<Peer> {
+    entries.iter().map(|entry| Peer {
+        collector_id: item.collector_id.clone(),
+        asn: entry.asn,
+        ip: entry.ip,
+        bgp_id: entry.bgp_id,
+        first_seen: item.ts_start,
+        last_seen: item.ts_start,
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::io::Write;
+    use crate::fetch::FetchConfig;
+    use crate::test_server::{serve, Response};
+
+    /// Encode an MRT `PEER_INDEX_TABLE` record with the given peers.
+    fn peer_index_table(peers: &[PeerEntry]) -> Vec<u8> {
+        let mut body = vec![];
+        body.extend_from_slice(&[192, 0, 2, 1]);
+        body.extend_from_slice(&4u16.to_be_bytes());
+        body.extend_from_slice(b"test");
+        body.extend_from_slice(&(peers.len() as u16).to_be_bytes());
+        for peer in peers {
+            let as4 = peer.asn > u16::MAX as u32;
+            let peer_type = if peer.ip.is_ipv6() { 0x01 } else { 0x00 } | if as4 { 0x02 } else { 0x00 };
+            body.push(peer_type);
+            body.extend_from_slice(&peer.bgp_id.octets());
+            match peer.ip {
+                IpAddr::V4(ip) => body.extend_from_slice(&ip.octets()),
+                IpAddr::V6(ip) => body.extend_from_slice(&ip.octets()),
+            }
+            match as4 {
+                true => body.extend_from_slice(&peer.asn.to_be_bytes()),
+                false => body.extend_from_slice(&(peer.asn as u16).to_be_bytes()),
+            }
+        }
+        let mut record = vec![];
+        record.extend_from_slice(&1664582400u32.to_be_bytes());
+        record.extend_from_slice(&TABLE_DUMP_V2.to_be_bytes());
+        record.extend_from_slice(&PEER_INDEX_TABLE.to_be_bytes());
+        record.extend_from_slice(&(body.len() as u32).to_be_bytes());
+        record.extend_from_slice(&body);
+        // a RIB entry follows the table
+        record.extend_from_slice(&[0u8; 64]);
+        record
+    }
+
+    fn test_peers() -> Vec<PeerEntry> {
+        vec![
+            PeerEntry { asn: 3333, ip: "193.0.0.56".parse().unwrap(), bgp_id: "193.0.0.56".parse().unwrap() },
+            PeerEntry { asn: 4200000000, ip: "2001:db8::1".parse().unwrap(), bgp_id: "10.0.0.1".parse().unwrap() },
+        ]
+    }
+
+    /// Many peers with pseudo-random IPv6 addresses, whose table does not compress to the
+    /// initial range.
+    fn many_peers() -> Vec<PeerEntry> {
+        let mut state = 1u64;
+        (0..6000u32).map(|i| {
+            let mut octets = [0u8; 16];
+            for octet in octets.iter_mut() {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                *octet = (state >> 56) as u8;
+            }
+            PeerEntry { asn: 64512 + i, ip: IpAddr::V6(Ipv6Addr::from(octets)), bgp_id: Ipv4Addr::from(i) }
+        }).collect()
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn bzip2(data: &[u8]) -> Vec<u8> {
+        let mut encoder = bzip2::write::BzEncoder::new(vec![], bzip2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    /// Serve the files by path, honoring `Range: bytes=0-N` headers, and 404 for anything else.
+    async fn start_server(files: HashMap<String, Vec<u8>>) -> String {
+        serve(move |request| match files.get(request.path.as_str()) {
+            Some(file) => Response::range(&request, file),
+            None => Response::not_found(),
+        }).await
+    }
+
+    #[test]
+    fn test_read_peer_index_table() {
+        let table = peer_index_table(&test_peers());
+        assert_eq!(read_peer_index_table(table.as_slice()).unwrap(), Some(test_peers()));
+
+        let truncated = read_peer_index_table(&table[..20]).unwrap_err();
+        assert_eq!(truncated.kind(), ErrorKind::UnexpectedEof);
+
+        // a legacy TABLE_DUMP record
+        let mut legacy = table.clone();
+        legacy[4..6].copy_from_slice(&12u16.to_be_bytes());
+        assert_eq!(read_peer_index_table(legacy.as_slice()).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_index_rib() {
+        let table = peer_index_table(&test_peers());
+        let large = gzip(&peer_index_table(&many_peers()));
+        assert!(large.len() > INITIAL_RANGE);
+        let mut legacy = table.clone();
+        legacy[4..6].copy_from_slice(&12u16.to_be_bytes());
+        let base = start_server(HashMap::from([
+            ("/bview.gz".to_string(), gzip(&table)),
+            ("/rib.bz2".to_string(), bzip2(&table)),
+            ("/large.gz".to_string(), large),
+            ("/legacy.bz2".to_string(), bzip2(&legacy)),
+        ])).await;
+
+        let fetcher = Fetcher::new(FetchConfig { max_retries: 0, ..Default::default() });
+        let url = |path: &str| format!("{}{}", base, path);
+        assert_eq!(index_rib(&fetcher, url("/bview.gz").as_str()).await, PeerIndexStatus::Peers(test_peers()));
+        assert_eq!(index_rib(&fetcher, url("/rib.bz2").as_str()).await, PeerIndexStatus::Peers(test_peers()));
+        assert_eq!(index_rib(&fetcher, url("/large.gz").as_str()).await, PeerIndexStatus::Peers(many_peers()));
+        assert_eq!(index_rib(&fetcher, url("/legacy.bz2").as_str()).await, PeerIndexStatus::NoPeerTable);
+        assert_eq!(index_rib(&fetcher, url("/gone.gz").as_str()).await, PeerIndexStatus::Missing);
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_index_collector_peers() {
+        use chrono::NaiveDateTime;
+        use crate::db::MISSING_AFTER_NOT_FOUND;
+        use crate::db::test_utils::test_collector;
+        use crate::db::models::{Collector, DataType, ItemFilter, PeerFilter};
+
+        let base = start_server(HashMap::from([
+            ("/bview.20221001.0000.gz".to_string(), gzip(&peer_index_table(&test_peers()))),
+            ("/bview.20221001.0800.gz".to_string(), gzip(&peer_index_table(&test_peers()[..1]))),
+        ])).await;
+        let db = DbConnection::new("sqlite::memory:").await.unwrap();
+        db.insert_collectors(&[Collector{ url: base.clone(), ..test_collector() }]).await.unwrap();
+        let ts = NaiveDateTime::parse_from_str("2022-10-01T00:00:00", "%Y-%m-%dT%H:%M:%S").unwrap();
+        let items = ["0000", "0800", "1600"].iter().enumerate().map(|(i, time)| Item{
+            ts_start: ts + chrono::Duration::hours(8 * i as i64),
+            ts_end: ts + chrono::Duration::hours(8 * i as i64),
+            collector_id: "rrc00".to_string(),
+            data_type: DataType::BgpTableDump,
+            url: format!("{}/bview.20221001.{}.gz", base, time),
+            rough_size: 0,
+            exact_size: 0
+        }).collect::<Vec<Item>>();
+        db.insert_items(&items).await.unwrap();
+
+        index_collector_peers("rrc00", &db, 10).await.unwrap();
+        // the third dump is missing, it is retried until flagged as missing
+        assert_eq!(db.get_unindexed_ribs("rrc00", 10).await.unwrap().len(), 1);
+        for _ in 1..MISSING_AFTER_NOT_FOUND {
+            index_collector_peers("rrc00", &db, 10).await.unwrap();
+        }
+        assert!(db.get_unindexed_ribs("rrc00", 10).await.unwrap().is_empty());
+
+        let peers = db.get_peers(&PeerFilter::default()).await.unwrap();
+        assert_eq!(peers.len(), 2);
+        assert_eq!((peers[0].asn, peers[0].first_seen, peers[0].last_seen), (3333, items[0].ts_start, items[1].ts_start));
+        assert_eq!((peers[1].asn, peers[1].first_seen, peers[1].last_seen), (4200000000, items[0].ts_start, items[0].ts_start));
+        let filter = PeerFilter { ip: Some("2001:db8::1".parse().unwrap()), ..Default::default() };
+        assert_eq!(db.get_peers(&filter).await.unwrap().len(), 1);
+
+        // items match peers from the first dump listing them up to the next indexed dump not
+        // listing them, and after the newest indexed dump if it lists them
+        let updates = [4, 12].iter().map(|hour| Item {
+            ts_start: ts + chrono::Duration::hours(*hour),
+            ts_end: ts + chrono::Duration::hours(*hour) + chrono::Duration::minutes(5),
+            data_type: DataType::BgpUpdate,
+            url: format!("{}/updates.20221001.{:02}00.gz", base, hour),
+            ..items[0].clone()
+        }).collect::<Vec<Item>>();
+        db.insert_items(&updates).await.unwrap();
+        let filter = ItemFilter { peer_asn: Some(3333), ..Default::default() };
+        assert_eq!(db.search_items(&filter, 1, 10).await.unwrap(), vec![items[0].clone(), updates[0].clone(), items[1].clone(), updates[1].clone()]);
+        let filter = ItemFilter { peer_asn: Some(4200000000), ..Default::default() };
+        assert_eq!(db.search_items(&filter, 1, 10).await.unwrap(), vec![items[0].clone(), updates[0].clone()]);
+        let filter = ItemFilter { peer_asn: Some(64500), ..Default::default() };
+        assert!(db.search_items(&filter, 1, 10).await.unwrap().is_empty());
+        let filter = ItemFilter { peer_ip: Some("193.0.0.56".parse().unwrap()), data_type: Some(DataType::BgpTableDump), ..Default::default() };
+        assert_eq!(db.search_items(&filter, 1, 10).await.unwrap().len(), 2);
+        let filter = ItemFilter { peer_ip: Some("2001:db8::1".parse().unwrap()), data_type: Some(DataType::BgpTableDump), ..Default::default() };
+        assert_eq!(db.search_items(&filter, 1, 10).await.unwrap(), items[..1].to_vec());
+    }
+}
@@ -0,0 +1,168 @@
+use crate::scrapers::*;
+use log::info;
+use futures::StreamExt;
+
+/// Scraper for frozen archives of retired collection projects, e.g. Isolario.
+///
+/// Frozen archives are static directory listings with one directory per month, named `YYYY.MM`
+/// or `YYYY_MM`, each containing RIB dumps and updates files, e.g.
+/// https://www.isolario.it/Isolario_MRT_data/Alderaan/2018_01/updates.20180101.0000.bz2
+///
+/// Since archives no longer change, projects using this scraper should be marked as `archival`
+/// in the collectors config, so that they are only indexed in `Bootstrap` and `Range` modes.
+pub struct ArchiveScraper {
+    pub mode: CrawlMode,
+    /// time between two table dumps, in seconds
+    pub rib_interval: i64,
+    /// time covered by each updates file, in seconds
+    pub update_interval: i64,
+}
+
+#[async_trait]
+impl Scraper for ArchiveScraper {
+    async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
+        info!("scraping archive collector {}; mode = {}", collector.id, &self.mode);
+
+        // months are taken from the root listing in every mode, as archives name their month
+        // directories either `YYYY.MM` or `YYYY_MM`
+        let month_link_pattern: Regex = Regex::new(r#"<a href="((\d{4})[._](\d{2}))/">.*"#).unwrap();
+        let body = fetcher().get_text(collector.url.as_str()).await?;
+        let listed = month_link_pattern.captures_iter(body.as_str())
+            .filter_map(|cap| Some((cap[1].to_owned(), (cap[2].parse::<i32>().ok()?, cap[3].parse::<u32>().ok()?))))
+            .collect::<Vec<(String, (i32, u32))>>();
+
+        let months = match self.mode {
+            CrawlMode::Latest | CrawlMode::TwoMonths | CrawlMode::Range {..} => {
+                let wanted = self.mode.months();
+                listed.into_iter()
+                    .filter(|(_, year_month)| wanted.contains(year_month))
+                    .map(|(month, _)| month)
+                    .collect()
+            }
+            CrawlMode::Bootstrap => {
+                let mut res = vec![];
+                for (month, _) in listed {
+                    if let Some(conn) = db {
+                        if conn.count_records_in_month(collector.id.as_str(), month.replace('_', ".").as_str()).await? > 0 {
+                            info!("skip month {} for {} in bootstrap mode", month.as_str(), collector.id.as_str());
+                            continue
+                        }
+                    }
+                    res.push(month)
+                }
+                res
+            }
+        };
+
+        info!("total of {} months to scrape", months.len());
+
+        let stream = futures::stream::iter(months).map(|month| {
+            let url = format!("{}/{}", collector.url, month);
+            self.scrape_month(url, month.replace('_', "."), collector.id.clone(), db)
+        }).buffer_unordered(100);
+        collect_results(collector.id.as_str(), stream).await
+    }
+
+    fn expected_interval(&self, data_type: DataType) -> Option<chrono::Duration> {
+        match data_type {
+            DataType::BgpTableDump => Some(chrono::Duration::seconds(self.rib_interval)),
+            DataType::BgpUpdate => Some(chrono::Duration::seconds(self.update_interval)),
+        }
+    }
+}
+
+impl ArchiveScraper {
+    async fn scrape_month(&self, url: String, month: String, collector_id: String, db: Option<&DbConnection>) -> Result<Vec<Item>, ScrapeError> {
+        info!("scraping data for {} {} ...", collector_id.as_str(), &month);
+        let body = fetcher().get_text(url.as_str()).await?;
+        info!("    download for {} {} finished ", collector_id.as_str(), &month);
+
+        let collector_clone = collector_id.clone();
+        let update_interval = self.update_interval;
+
+        let mut data_items: Vec<Item> =
+        tokio::task::spawn_blocking(move || {
+            let items = extract_link_size(body.as_str())?;
+            Ok::<_, ScrapeError>(items.iter().filter_map(|(link, size)| {
+                let file_url = format!("{}/{}", url, link);
+                parse_file_item(file_url, *size, collector_id.as_str(), update_interval)
+            }).collect())
+        }).await??;
+
+        if let Some(conn) = db {
+            data_items = insert_new_items(self.mode, conn, collector_clone.as_str(), month.as_str(), data_items).await?;
+        }
+
+        info!("scraping data for {} ... finished", &month);
+        Ok(data_items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_items() {
+        const ISOLARIO: &str = r#"<html>
+<head><title>Index of /Isolario_MRT_data/Alderaan/2018_01/</title></head>
+<body>
+<h1>Index of /Isolario_MRT_data/Alderaan/2018_01/</h1><hr><pre><a href="../">../</a>
+<a href="rib.20180101.0000.bz2">rib.20180101.0000.bz2</a>                              01-Jan-2018 00:13     68M
+<a href="updates.20180101.0000.bz2">updates.20180101.0000.bz2</a>                          01-Jan-2018 00:05    203K
+<a href="updates.20180101.0005.bz2">updates.20180101.0005.bz2</a>                          01-Jan-2018 00:10    198K
+</pre><hr></body>
+</html>
+"#;
+        let url = "https://www.isolario.it/Isolario_MRT_data/Alderaan/2018_01";
+        let items: Vec<Item> = extract_link_size(ISOLARIO).unwrap().into_iter()
+            .filter_map(|(link, size)| parse_file_item(format!("{}/{}", url, link), size, "Alderaan", 5*60))
+            .collect();
+        assert_eq!(items.len(), 3);
+
+        assert_eq!(items[0].data_type, DataType::BgpTableDump);
+        assert_eq!(items[0].ts_start, items[0].ts_end);
+        assert_eq!(items[0].rough_size, 68*1024*1024);
+
+        assert_eq!(items[2].data_type, DataType::BgpUpdate);
+        assert_eq!(items[2].ts_start, NaiveDateTime::parse_from_str("2018-01-01 00:05", "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(items[2].ts_end, NaiveDateTime::parse_from_str("2018-01-01 00:10", "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(items[2].url, "https://www.isolario.it/Isolario_MRT_data/Alderaan/2018_01/updates.20180101.0005.bz2");
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_scrape_fixtures() {
+        use crate::scrapers::fixtures::serve;
+
+        // the recorded Isolario archive names its month directories `YYYY_MM`
+        let server = serve("isolario", vec![]).await;
+        let collector = Collector {
+            id: "Alderaan".to_string(),
+            project: Project::Isolario,
+            url: format!("{}/Alderaan", server.url),
+            meta: Default::default()
+        };
+        let modes = [
+            (CrawlMode::new("range", Some("2018-01"), Some("2018-01")).unwrap(), 3, "2018_01/updates.20180101.0005.bz2"),
+            (CrawlMode::Bootstrap, 5, "2018_02/updates.20180201.0000.bz2"),
+        ];
+        for (mode, total, last) in modes {
+            let db = DbConnection::new("sqlite::memory:").await.unwrap();
+            db.insert_collectors(std::slice::from_ref(&collector)).await.unwrap();
+            let scraper = ArchiveScraper { mode, rib_interval: 2*60*60, update_interval: 5*60 };
+            scraper.scrape(&collector, Some(&db)).await.unwrap();
+
+            let items = db.search_items(&ItemFilter::default(), 1, 10).await.unwrap();
+            assert_eq!(items.len(), total, "{}", mode);
+            assert_eq!(items[0].url, format!("{}/2018_01/rib.20180101.0000.bz2", collector.url));
+            assert_eq!(items.last().unwrap().url, format!("{}/{}", collector.url, last), "{}", mode);
+        }
+
+        // months not listed in the archive are not requested
+        let db = DbConnection::new("sqlite::memory:").await.unwrap();
+        let scraper = ArchiveScraper { mode: CrawlMode::new("range", Some("2019-01"), Some("2019-02")).unwrap(), rib_interval: 2*60*60, update_interval: 5*60 };
+        scraper.scrape(&collector, Some(&db)).await.unwrap();
+        assert!(db.search_items(&ItemFilter::default(), 1, 10).await.unwrap().is_empty());
+    }
+}
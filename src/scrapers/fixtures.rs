@@ -0,0 +1,112 @@
+//! Local HTTP server for offline scraper tests.
+//!
+//! Directory listings recorded from the data sources live in `tests/fixtures/<tree>/`, with the
+//! listing of each directory in its `index.html`. Every listing carries an `ETag`, so conditional
+//! requests of the `Latest` and `TwoMonths` modes are answered with `304 Not Modified` when
+//! repeated.
+//!
+//! The recorded listings are from fixed months, while the `Latest` and `TwoMonths` modes crawl the
+//! current months. Month aliases serve a recorded month under another one, in `YYYY.MM` or
+//! `YYYY/MM` directories, rewriting the timestamps in the listing to the served month.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use crate::scrapers::CrawlMode;
+use crate::test_server::{self, Response};
+
+/// Months of the recorded listings, in order.
+pub(crate) const RECORDED_MONTHS: [&str; 2] = ["2022.10", "2022.11"];
+
+pub(crate) struct FixtureServer {
+    /// base URL of the server, without a trailing slash
+    pub url: String,
+    /// number of requests answered with `304 Not Modified`
+    pub not_modified: Arc<AtomicUsize>,
+}
+
+/// Aliases serving the recorded months as the months crawled in a mode, the latest recorded
+/// month as the latest crawled month. Empty for modes crawling fixed months.
+pub(crate) fn month_aliases(mode: CrawlMode) -> Vec<(String, String)> {
+    match mode {
+        CrawlMode::Latest | CrawlMode::TwoMonths => mode.months().iter().rev()
+            .zip(RECORDED_MONTHS.iter().rev())
+            .map(|((year, month), recorded)| (format!("{}.{:02}", year, month), recorded.to_string()))
+            .collect(),
+        CrawlMode::Bootstrap | CrawlMode::Range {..} => vec![],
+    }
+}
+
+/// The `(served, recorded)` months a scraper crawls in a mode, in order.
+pub(crate) fn scraped_months(mode: CrawlMode) -> Vec<(String, String)> {
+    match mode {
+        CrawlMode::Latest | CrawlMode::TwoMonths => month_aliases(mode),
+        CrawlMode::Bootstrap => RECORDED_MONTHS.iter().map(|m| (m.to_string(), m.to_string())).collect(),
+        CrawlMode::Range {..} => mode.months().iter()
+            .map(|(year, month)| (format!("{}.{:02}", year, month), format!("{}.{:02}", year, month)))
+            .collect(),
+    }
+}
+
+/// Separators between year and month in directory and file names, e.g. `YYYY.MM` of RIS,
+/// `YYYY/MM` of PCH directories and `YYYY-MM-DD` of PCH files.
+const MONTH_SEPARATORS: [&str; 4] = [".", "", "-", "/"];
+
+/// Rewrite the timestamps of one `YYYY.MM` month to another, in all separator forms.
+fn rewrite_month(body: &str, from: &str, to: &str) -> String {
+    MONTH_SEPARATORS.iter().fold(body.to_string(), |body, sep| {
+        body.replace(from.replace('.', sep).as_str(), to.replace('.', sep).as_str())
+    })
+}
+
+fn etag(body: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    format!("\"{:x}\"", hasher.finish())
+}
+
+/// Find the listing for a request path, applying the `(served, recorded)` month aliases.
+fn read_listing(tree: &str, path: &str, aliases: &[(String, String)]) -> Option<String> {
+    let mut path = format!("/{}/", path.split('?').next().unwrap_or_default().trim_matches('/'));
+    let mut alias = None;
+    for (served, recorded) in aliases {
+        for sep in [".", "/"] {
+            let served_dir = format!("/{}/", served.replace('.', sep));
+            if path.contains(served_dir.as_str()) {
+                path = path.replace(served_dir.as_str(), format!("/{}/", recorded.replace('.', sep)).as_str());
+                alias = Some((served, recorded));
+            }
+        }
+    }
+    let path = path.trim_matches('/');
+    let file = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(tree).join(path).join("index.html");
+    let body = std::fs::read_to_string(file).ok()?;
+    Some(match alias {
+        Some((served, recorded)) => rewrite_month(body.as_str(), recorded, served),
+        None => body,
+    })
+}
+
+/// Serve the recorded tree `tests/fixtures/<tree>/` with the given `(served, recorded)` month
+/// aliases.
+pub(crate) async fn serve(tree: &'static str, aliases: Vec<(String, String)>) -> FixtureServer {
+    let not_modified = Arc::new(AtomicUsize::new(0));
+    let counter = not_modified.clone();
+    let url = test_server::serve(move |request| {
+        let body = match read_listing(tree, request.path.as_str(), &aliases) {
+            Some(body) => body,
+            None => return Response::not_found(),
+        };
+        let etag = etag(body.as_str());
+        match request.header("if-none-match") == Some(etag.as_str()) {
+            true => {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Response::new("304 Not Modified").header("ETag", etag.as_str())
+            }
+            false => Response::ok(body).header("ETag", etag.as_str()).header("Content-Type", "text/html"),
+        }
+    }).await;
+    FixtureServer { url, not_modified }
+}
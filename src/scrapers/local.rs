@@ -0,0 +1,225 @@
+use std::path::{Path, PathBuf};
+use crate::scrapers::*;
+use log::info;
+use futures::StreamExt;
+
+/// Directory layout of a locally mirrored collector.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LocalLayout {
+    /// `YYYY.MM/RIBS/rib.YYYYMMDD.HHMM.bz2` and `YYYY.MM/UPDATES/updates.YYYYMMDD.HHMM.bz2`
+    RouteViews,
+    /// `YYYY.MM/bview.YYYYMMDD.HHMM.gz` and `YYYY.MM/updates.YYYYMMDD.HHMM.gz`
+    RipeRis,
+}
+
+impl LocalLayout {
+    /// Subdirectories of a month directory that contain data files.
+    fn month_subdirs(&self) -> Vec<&'static str> {
+        match self {
+            LocalLayout::RouteViews => vec!["RIBS", "UPDATES"],
+            LocalLayout::RipeRis => vec![""],
+        }
+    }
+
+    /// Time between two table dumps, in seconds.
+    fn rib_interval(&self) -> i64 {
+        match self {
+            LocalLayout::RouteViews => 2*60*60,
+            LocalLayout::RipeRis => 8*60*60,
+        }
+    }
+
+    /// Time covered by each updates file, in seconds.
+    fn update_interval(&self) -> i64 {
+        match self {
+            LocalLayout::RouteViews => 15*60,
+            LocalLayout::RipeRis => 5*60,
+        }
+    }
+}
+
+impl FromStr for LocalLayout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "routeviews" => Ok(LocalLayout::RouteViews),
+            "riperis" => Ok(LocalLayout::RipeRis),
+            _ => Err("local layout must be one of the: ['routeviews', 'riperis']".to_string())
+        }
+    }
+}
+
+/// Scraper for collectors mirrored on a local filesystem, e.g. an NFS mount of RouteViews or RIS.
+///
+/// The collector URL is the root directory of the mirrored collector, either as a `file://` URL
+/// or a plain path, e.g. `file:///mnt/mirror/route-views2/bgpdata`. Items get exact sizes from
+/// the filesystem, and `file://` URLs unless a `url_prefix` is set.
+pub struct LocalScraper {
+    pub mode: CrawlMode,
+    pub layout: LocalLayout,
+    /// URL of the mirror root, under which each collector's files are published in a directory
+    /// named after the collector ID, e.g. `http://mirror.internal/bgp`
+    pub url_prefix: Option<String>,
+}
+
+#[async_trait]
+impl Scraper for LocalScraper {
+    async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
+        info!("scraping local collector {}; mode = {}", collector.id, &self.mode);
+        let root = PathBuf::from(collector.url.trim_start_matches("file://"));
+
+        let months = match self.mode {
+            CrawlMode::Latest | CrawlMode::TwoMonths | CrawlMode::Range {..} => {
+                self.mode.months().into_iter()
+                    .map(|(year, month)| format!("{}.{:02}", year, month))
+                    .collect()
+            }
+            CrawlMode::Bootstrap => {
+                let root_clone = root.clone();
+                let months = tokio::task::spawn_blocking(move || list_months(root_clone.as_path())).await??;
+                let mut res = vec![];
+                for month in months {
+                    if let Some(conn) = db {
+                        if conn.count_records_in_month(collector.id.as_str(), month.as_str()).await? > 0 {
+                            info!("skip month {} for {} in bootstrap mode", month.as_str(), collector.id.as_str());
+                            continue
+                        }
+                    }
+                    res.push(month)
+                }
+                res
+            }
+        };
+
+        info!("total of {} months to scrape", months.len());
+
+        let stream = futures::stream::iter(months).map(|month| {
+            self.scrape_month(root.clone(), month, collector.id.clone(), db)
+        }).buffer_unordered(10);
+        collect_results(collector.id.as_str(), stream).await
+    }
+
+    fn expected_interval(&self, data_type: DataType) -> Option<chrono::Duration> {
+        match data_type {
+            DataType::BgpTableDump => Some(chrono::Duration::seconds(self.layout.rib_interval())),
+            DataType::BgpUpdate => Some(chrono::Duration::seconds(self.layout.update_interval())),
+        }
+    }
+}
+
+impl LocalScraper {
+    async fn scrape_month(&self, root: PathBuf, month: String, collector_id: String, db: Option<&DbConnection>) -> Result<Vec<Item>, ScrapeError> {
+        info!("scraping data for {} {} ...", collector_id.as_str(), &month);
+
+        let collector_clone = collector_id.clone();
+        let layout = self.layout;
+        let url_prefix = self.url_prefix.clone();
+        let month_clone = month.clone();
+
+        let mut data_items: Vec<Item> =
+        tokio::task::spawn_blocking(move || {
+            Ok::<_, ScrapeError>(list_month_files(root.as_path(), month_clone.as_str(), layout)?.into_iter().filter_map(|(relative_path, size)| {
+                let url = match &url_prefix {
+                    Some(prefix) => format!("{}/{}/{}", prefix.trim_end_matches('/'), collector_id, relative_path),
+                    None => format!("file://{}", root.join(&relative_path).display()),
+                };
+                let mut item = parse_file_item(url, size, collector_id.as_str(), layout.update_interval())?;
+                item.exact_size = size;
+                Some(item)
+            }).collect())
+        }).await??;
+
+        if let Some(conn) = db {
+            data_items = insert_new_items(self.mode, conn, collector_clone.as_str(), month.as_str(), data_items).await?;
+        }
+
+        Ok(data_items)
+    }
+}
+
+/// List the `YYYY.MM` month directories of a collector root, in order.
+fn list_months(root: &Path) -> std::io::Result<Vec<String>> {
+    let month_pattern: Regex = Regex::new(r#"^\d{4}\.\d{2}$"#).unwrap();
+    let mut months = vec![];
+    for entry in std::fs::read_dir(root)? {
+        let month = entry?.file_name().to_string_lossy().to_string();
+        if month_pattern.is_match(month.as_str()) {
+            months.push(month);
+        }
+    }
+    months.sort();
+    Ok(months)
+}
+
+/// List files of a month directory with their sizes, as paths relative to the collector root.
+///
+/// Missing directories are treated as empty, any other filesystem error fails the listing.
+fn list_month_files(root: &Path, month: &str, layout: LocalLayout) -> std::io::Result<Vec<(String, i64)>> {
+    let mut files = vec![];
+    for subdir in layout.month_subdirs() {
+        let relative_dir = match subdir.is_empty() {
+            true => month.to_string(),
+            false => format!("{}/{}", month, subdir),
+        };
+        let entries = match std::fs::read_dir(root.join(&relative_dir)) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e)
+        };
+        for entry in entries {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            if !meta.is_file() {
+                continue
+            }
+            files.push((format!("{}/{}", relative_dir, entry.file_name().to_string_lossy()), meta.len() as i64));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_local_routeviews() {
+        let root = std::env::temp_dir().join(format!("bgpkit-broker-local-test-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("2022.10/RIBS")).unwrap();
+        std::fs::create_dir_all(root.join("2022.10/UPDATES")).unwrap();
+        std::fs::write(root.join("2022.10/RIBS/rib.20221001.0000.bz2"), vec![0u8; 100]).unwrap();
+        std::fs::write(root.join("2022.10/UPDATES/updates.20221001.0000.bz2"), vec![0u8; 10]).unwrap();
+        std::fs::write(root.join("2022.10/UPDATES/updates.20221001.0015.bz2"), vec![0u8; 20]).unwrap();
+        std::fs::write(root.join("2022.10/UPDATES/README"), vec![0u8; 20]).unwrap();
+
+        let scraper = LocalScraper{ mode: CrawlMode::Bootstrap, layout: LocalLayout::RouteViews, url_prefix: None };
+        let items = scraper.scrape_month(root.clone(), "2022.10".to_string(), "route-views2".to_string(), None).await.unwrap();
+        assert_eq!(items.len(), 3);
+        assert_eq!(items[0].data_type, DataType::BgpTableDump);
+        assert_eq!(items[0].exact_size, 100);
+        assert_eq!(items[0].url, format!("file://{}", root.join("2022.10/RIBS/rib.20221001.0000.bz2").display()));
+        assert_eq!(items[2].data_type, DataType::BgpUpdate);
+        assert_eq!(items[2].exact_size, 20);
+        assert_eq!(items[2].ts_end - items[2].ts_start, chrono::Duration::minutes(15));
+
+        let scraper = LocalScraper{ mode: CrawlMode::Bootstrap, layout: LocalLayout::RouteViews, url_prefix: Some("http://mirror.internal/bgp/".to_string()) };
+        let items = scraper.scrape_month(root.clone(), "2022.10".to_string(), "route-views2".to_string(), None).await.unwrap();
+        assert_eq!(items[0].url, "http://mirror.internal/bgp/route-views2/2022.10/RIBS/rib.20221001.0000.bz2");
+
+        let scraper = LocalScraper{ mode: CrawlMode::Bootstrap, layout: LocalLayout::RipeRis, url_prefix: None };
+        let items = scraper.scrape_month(root.clone(), "2022.10".to_string(), "rrc00".to_string(), None).await.unwrap();
+        assert!(items.is_empty());
+
+        // missing month directories are empty, unreadable ones fail the scrape
+        assert!(list_month_files(root.as_path(), "2022.09", LocalLayout::RouteViews).unwrap().is_empty());
+        std::fs::write(root.join("2022.11"), vec![0u8; 10]).unwrap();
+        assert!(list_month_files(root.as_path(), "2022.11", LocalLayout::RipeRis).is_err());
+        assert!(scraper.scrape_month(root.clone(), "2022.11".to_string(), "rrc00".to_string(), None).await.is_err());
+        assert_eq!(list_months(root.as_path()).unwrap(), vec!["2022.10", "2022.11"]);
+        assert!(list_months(root.join("missing").as_path()).is_err());
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
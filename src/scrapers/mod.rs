@@ -1,28 +1,48 @@
 pub mod routeviews;
 pub mod riperis;
+pub mod pch;
+pub mod archive;
+pub mod local;
+pub mod registry;
 mod utils;
+#[cfg(all(test, feature = "sqlite"))]
+pub(crate) mod fixtures;
 
 use std::fmt::{Display, Formatter};
 use std::str::FromStr;
 use crate::db::models::*;
 use crate::errors::*;
 use regex::Regex;
-use chrono::NaiveDateTime;
+use chrono::{Datelike, NaiveDateTime, Utc};
+use log::{info, warn};
+use futures::{Stream, StreamExt};
 use scraper::{Html, Selector};
 
 pub use routeviews::RouteViewsScraper;
 pub use riperis::RipeRisScraper;
+pub use pch::PchScraper;
+pub use archive::ArchiveScraper;
+pub use local::{LocalLayout, LocalScraper};
+pub use registry::ScraperRegistry;
 use crate::db::DbConnection;
+use crate::fetch::{fetcher, Listing, Validators};
+use crate::metrics::{count_items, metrics};
+use async_trait::async_trait;
 
 const SIZE_KB: u64 = u64::pow(1024,1);
 const SIZE_MB: u64 = u64::pow(1024,2);
 const SIZE_GB: u64 = u64::pow(1024,3);
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
 pub enum CrawlMode {
     Latest,
     Bootstrap,
-    TwoMonths
+    TwoMonths,
+    /// months from `from` to `to` as `(year, month)` pairs, both inclusive
+    Range {
+        from: (i32, u32),
+        to: (i32, u32),
+    },
 }
 
 impl Display for CrawlMode {
@@ -31,6 +51,9 @@ impl Display for CrawlMode {
             CrawlMode::Latest => {write!(f, "latest")}
             CrawlMode::Bootstrap => {write!(f, "bootstrap")}
             CrawlMode::TwoMonths => {write!(f, "two_months")}
+            CrawlMode::Range {from, to} => {
+                write!(f, "range {}-{:02} to {}-{:02}", from.0, from.1, to.0, to.1)
+            }
         }
     }
 }
@@ -43,31 +66,235 @@ impl FromStr for CrawlMode {
             "latest" => Ok(CrawlMode::Latest),
             "two_months" => Ok(CrawlMode::TwoMonths),
             "bootstrap" => Ok(CrawlMode::Bootstrap),
-            _ => Err("crawl mode must be one of the: ['latest', 'two_months', 'bootstrap']".to_string())
+            "range" => Err("range mode requires from and to months, create it with CrawlMode::new".to_string()),
+            _ => Err("crawl mode must be one of the: ['latest', 'two_months', 'bootstrap', 'range']".to_string())
+        }
+    }
+}
+
+/// Parse a month string in `YYYY-MM` or `YYYY.MM` format into a `(year, month)` pair.
+fn parse_month(month_str: &str) -> Result<(i32, u32), String> {
+    let err = || format!("invalid month {}, expecting YYYY-MM format", month_str);
+    let (year, month) = month_str.split_once(['-', '.']).ok_or_else(err)?;
+    let year = year.parse::<i32>().map_err(|_| err())?;
+    let month = month.parse::<u32>().map_err(|_| err())?;
+    if !(1..=12).contains(&month) {
+        return Err(err())
+    }
+    Ok((year, month))
+}
+
+impl CrawlMode {
+    /// Create a crawl mode from its name, with `from` and `to` months in `YYYY-MM` format
+    /// required by the `range` mode.
+    pub fn new(mode: &str, from: Option<&str>, to: Option<&str>) -> Result<CrawlMode, String> {
+        if mode != "range" {
+            return CrawlMode::from_str(mode)
+        }
+        let from = parse_month(from.ok_or("range mode requires a from month")?)?;
+        let to = parse_month(to.ok_or("range mode requires a to month")?)?;
+        if from > to {
+            return Err(format!("range from {}-{:02} is after to {}-{:02}", from.0, from.1, to.0, to.1))
+        }
+        Ok(CrawlMode::Range { from, to })
+    }
+
+    /// Start of the first month and start of the month after the last month of a `Range` mode,
+    /// `None` for other modes.
+    pub fn period(&self) -> Option<(NaiveDateTime, NaiveDateTime)> {
+        match self {
+            CrawlMode::Range {from, to} => {
+                let next = match to.1 {
+                    12 => (to.0 + 1, 1),
+                    month => (to.0, month + 1),
+                };
+                let start = chrono::NaiveDate::from_ymd_opt(from.0, from.1, 1)?.and_hms_opt(0, 0, 0)?;
+                let end = chrono::NaiveDate::from_ymd_opt(next.0, next.1, 1)?.and_hms_opt(0, 0, 0)?;
+                Some((start, end))
+            }
+            _ => None
+        }
+    }
+
+    /// Months to crawl as `(year, month)` pairs for `Latest`, `TwoMonths` and `Range` modes, empty
+    /// for `Bootstrap` where the months are listed from the collector's root page.
+    pub fn months(&self) -> Vec<(i32, u32)> {
+        match self {
+            CrawlMode::Latest => {
+                let ts = Utc::now();
+                let ts2 = ts - chrono::Duration::days(1);
+                if ts.month() == ts2.month() {
+                    vec![(ts.year(), ts.month())]
+                } else {
+                    // on borderline date, i.e. on the end of a month
+                    // we check both current and previous month to make sure we don't miss anything
+                    vec![(ts2.year(), ts2.month()), (ts.year(), ts.month())]
+                }
+            }
+            CrawlMode::TwoMonths => {
+                let ts = Utc::now();
+                let ts2 = utils::shift_months(ts, -1);
+                vec![(ts2.year(), ts2.month()), (ts.year(), ts.month())]
+            }
+            CrawlMode::Range {from, to} => {
+                let mut months = vec![];
+                let (mut year, mut month) = *from;
+                while (year, month) <= *to {
+                    months.push((year, month));
+                    if month == 12 {
+                        year += 1;
+                        month = 1;
+                    } else {
+                        month += 1;
+                    }
+                }
+                months
+            }
+            CrawlMode::Bootstrap => vec![]
+        }
+    }
+}
+
+/// Scraper for one MRT data collection project.
+///
+/// A scraper crawls the data publication site of a collector, and inserts newly found items into
+/// the database if a connection is given. Scrapers are looked up by project name through a
+/// [ScraperRegistry].
+#[async_trait]
+pub trait Scraper: Send + Sync {
+    /// Scrape one collector of the project.
+    async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError>;
+
+    /// Expected time between two consecutive files of a data type, `None` if the project has no
+    /// regular publishing cadence.
+    fn expected_interval(&self, _data_type: DataType) -> Option<chrono::Duration> {
+        None
+    }
+
+    /// Discover the collectors published under the project's root page, e.g. the RouteViews
+    /// archive root. Fails for projects without collector discovery.
+    async fn discover(&self, project: &Project, _root_url: &str) -> Result<Vec<Collector>, ScrapeError> {
+        Err(ScrapeError::ConfigError(format!("collector discovery is not supported for project {}", project)))
+    }
+}
+
+/// Insert items of one collector's month into the database and send notifications, returning
+/// the newly inserted items.
+///
+/// In `Latest`, `TwoMonths` and `Range` modes, items already in the database are filtered out before
+/// inserting.
+pub(crate) async fn insert_new_items(mode: CrawlMode, conn: &DbConnection, collector_id: &str, month: &str, data_items: Vec<Item>) -> Result<Vec<Item>, ScrapeError> {
+    info!("    insert to db for {} {}...", collector_id, month);
+    count_items(&metrics().items_parsed, collector_id, &data_items);
+
+    let to_insert = match mode {
+        CrawlMode::Latest | CrawlMode::TwoMonths | CrawlMode::Range {..} => {
+            let current_month_items = conn.get_urls_in_month(collector_id, month).await?;
+            data_items.into_iter().filter(|x|!current_month_items.contains(&x.url))
+                .collect::<Vec<Item>>()
         }
+        CrawlMode::Bootstrap => {
+            data_items
+        }
+    };
+
+    let timer = metrics().db_insert_seconds.with_label_values(&[]).start_timer();
+    let inserted = conn.insert_items(&to_insert).await?;
+    timer.observe_duration();
+    count_items(&metrics().items_inserted, collector_id, &inserted);
+    metrics().update_latest(&inserted);
+
+    conn.notify(&inserted).await;
+
+    info!("    insert to db for {} {}... {}/{} inserted", collector_id, month, to_insert.len(), inserted.len());
+    Ok(inserted)
+}
+
+/// Download a month listing. In `Latest` and `TwoMonths` modes, the request is conditional on the
+/// validators saved by the previous run, and `None` is returned if the listing did not change.
+///
+/// The returned validators should be saved with [DbConnection::save_listing_validators] once the
+/// listing's items are inserted.
+pub(crate) async fn fetch_listing(mode: CrawlMode, url: &str, db: Option<&DbConnection>) -> Result<Option<(String, Validators)>, ScrapeError> {
+    let validators = match (mode, db) {
+        (CrawlMode::Latest | CrawlMode::TwoMonths, Some(conn)) => conn.get_listing_validators(url).await?,
+        _ => None
+    };
+    match fetcher().get_listing(url, validators.as_ref()).await? {
+        Listing::NotModified => Ok(None),
+        Listing::Modified { body, validators } => Ok(Some((body, validators))),
     }
 }
 
-fn size_str_to_bytes(size_str: &str, size_pattern: &Regex) -> Option<i64> {
+/// Drain a stream of a collector's per-month scraping results, logging every failure and
+/// returning the first one, so that one broken month does not stop the others.
+pub(crate) async fn collect_results<S, T>(collector_id: &str, mut results: S) -> Result<(), ScrapeError>
+    where S: Stream<Item = Result<T, ScrapeError>> + Unpin
+{
+    let mut first_error = None;
+    while let Some(res) = results.next().await {
+        if let Err(e) = res {
+            warn!("scraping {} failed: {}", collector_id, e);
+            if first_error.is_none() {
+                first_error = Some(e);
+            }
+        }
+    }
+    match first_error {
+        None => Ok(()),
+        Some(e) => Err(e),
+    }
+}
+
+/// Convert the URL of a file named `{rib,bview,updates}.YYYYMMDD.HHMM.{gz,bz2}` into an [Item],
+/// `None` if the file name does not match.
+pub(crate) fn parse_file_item(url: String, size: i64, collector_id: &str, update_interval: i64) -> Option<Item> {
+    let file_pattern: Regex = Regex::new(r#"(rib|bview|updates)\.(\d{8}\.\d{4})\.(gz|bz2)$"#).unwrap();
+    let cap = file_pattern.captures(url.as_str())?;
+    let ts_start = NaiveDateTime::parse_from_str(&cap[2], "%Y%m%d.%H%M").ok()?;
+    let (data_type, ts_end) = match &cap[1] {
+        "updates" => (DataType::BgpUpdate, ts_start + chrono::Duration::seconds(update_interval)),
+        _ => (DataType::BgpTableDump, ts_start),
+    };
+    Some(Item {
+        ts_start,
+        ts_end,
+        collector_id: collector_id.to_string(),
+        data_type,
+        url,
+        rough_size: size,
+        exact_size: 0,
+    })
+}
+
+/// Parse a listed file size such as `12.3M` into bytes, `None` if the text is not a size.
+fn size_str_to_bytes(size_str: &str, size_pattern: &Regex) -> Result<Option<i64>, ScrapeError> {
     let cap = match size_pattern.captures(size_str) {
-        Some(x) => x,
-        None => return None
+        Some(cap) => cap,
+        None => return Ok(None)
     };
     let mut size = match cap[1].to_string().parse::<f64>() {
         Ok(x) => x,
-        Err(_) => return None
+        Err(_) => return Ok(None)
     };
     size *= match cap[2].to_ascii_lowercase().as_str() {
         "k" => SIZE_KB,
         "m" => SIZE_MB,
         "g" => SIZE_GB,
         "" => 1,
-        other => panic!("unknown file size multiplier {}", other)
+        other => return Err(ScrapeError::ParseError(format!("unknown file size multiplier {} in {}", other, size_str.trim())))
     } as f64;
-    Some(size as i64)
+    Ok(Some(size as i64))
+}
+
+/// Names of the subdirectories linked from a directory listing, e.g. `rrc00` for
+/// `<a href="rrc00/">`.
+pub(crate) fn extract_dir_links(body: &str) -> Vec<String> {
+    let dir_link_pattern: Regex = Regex::new(r#"<a href="([^"/?]+)/">"#).unwrap();
+    dir_link_pattern.captures_iter(body).map(|cap| cap[1].to_string()).collect()
 }
 
-pub fn extract_link_size(body: &str) -> Vec<(String, i64)>{
+pub fn extract_link_size(body: &str) -> Result<Vec<(String, i64)>, ScrapeError> {
     let mut res: Vec<(String, i64)> = vec![];
 
     if body.contains("table") {
@@ -82,37 +309,61 @@ pub fn extract_link_size(body: &str) -> Vec<(String, i64)>{
             if text.is_empty() || text.contains("Name") || text.contains("Parent") {
                 continue
             }
-            let href = elem.select(&link_selector).next().unwrap().value().attr("href");
-            let size = match size_str_to_bytes(text_arr[2], &size_pattern) {
+            let href = elem.select(&link_selector).next().and_then(|link| link.value().attr("href"))
+                .ok_or_else(|| ScrapeError::ParseError(format!("listing row without link: {}", text)))?;
+            let size = match text_arr.get(2) {
+                Some(size_str) => match size_str_to_bytes(size_str, &size_pattern)? {
+                    None => {continue}
+                    Some(v) => {v}
+                },
                 None => {continue}
-                Some(v) => {v}
             };
-            res.push((href.unwrap().to_string(), size));
+            res.push((href.to_string(), size));
         }
     } else {
+        let size_pattern: Regex = Regex::new(r#" +([\d.]+)([MKGmkg]*)$"#).unwrap();
         for line in body.lines() {
-            let size_pattern: Regex = Regex::new(r#" +([\d.]+)([MKGmkg]*)$"#).unwrap();
-            let size = size_str_to_bytes(line, &size_pattern);
-            if size.is_none() {
-                continue
-            }
+            let size = match size_str_to_bytes(line, &size_pattern)? {
+                None => {continue}
+                Some(v) => {v}
+            };
 
             let fragment = Html::parse_fragment(line);
             let link_selector = Selector::parse("a").unwrap();
             let mut link = "".to_string();
             if let Some(elem) = fragment.select(&link_selector).next() {
-                link = elem.value().attr("href").unwrap().to_string();
+                link = elem.value().attr("href")
+                    .ok_or_else(|| ScrapeError::ParseError(format!("listing link without href: {}", line)))?
+                    .to_string();
             }
-            res.push((link, size.unwrap()));
+            res.push((link, size));
         }
     }
-    res
+    Ok(res)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_range_mode() {
+        let mode = CrawlMode::new("range", Some("2018-11"), Some("2019.02")).unwrap();
+        assert_eq!(mode, CrawlMode::Range { from: (2018, 11), to: (2019, 2) });
+        assert_eq!(mode.months(), vec![(2018, 11), (2018, 12), (2019, 1), (2019, 2)]);
+        let ts = |s: &str| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap();
+        assert_eq!(mode.period(), Some((ts("2018-11-01 00:00"), ts("2019-03-01 00:00"))));
+        assert_eq!(CrawlMode::new("range", Some("2019-12"), Some("2019-12")).unwrap().period(), Some((ts("2019-12-01 00:00"), ts("2020-01-01 00:00"))));
+        assert_eq!(CrawlMode::Latest.period(), None);
+
+        assert_eq!(CrawlMode::new("latest", None, None).unwrap(), CrawlMode::Latest);
+        assert!(CrawlMode::from_str("range").unwrap_err().contains("from and to months"));
+        assert!(CrawlMode::new("weekly", None, None).unwrap_err().contains("'range'"));
+        assert!(CrawlMode::new("range", Some("2019-01"), None).is_err());
+        assert!(CrawlMode::new("range", Some("2019-06"), Some("2019-01")).is_err());
+        assert!(CrawlMode::new("range", Some("2019-13"), Some("2020-01")).is_err());
+    }
+
     #[test]
     fn test_extract_link_size() {
         const RIPE_OLD: &str = r#"<!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">
@@ -167,12 +418,15 @@ mod tests {
 </body></html>
 "#;
 
-        let res = extract_link_size(RIPE_OLD);
+        let res = extract_link_size(RIPE_OLD).unwrap();
         assert_eq!(res.len(),4);
-        let res = extract_link_size(RIPE_NEW);
+        let res = extract_link_size(RIPE_NEW).unwrap();
         assert_eq!(res.len(),4);
-        let res = extract_link_size(ROUTEVIEWS);
+        let res = extract_link_size(ROUTEVIEWS).unwrap();
         assert_eq!(res.len(),4);
+
+        let res = extract_link_size(r#"<a href="bview.20010101.0609.gz">bview.20010101.0609.gz</a>    01-Jan-2001 06:09     12MK"#);
+        assert!(matches!(res, Err(ScrapeError::ParseError(_))));
     }
 
 }
\ No newline at end of file
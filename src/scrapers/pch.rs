@@ -0,0 +1,221 @@
+use crate::scrapers::*;
+use log::info;
+use futures::StreamExt;
+
+/// PCH publishes one updates file and one table dump per collector per day.
+const PCH_UPDATE_INTERVAL: i64 = 24*60*60;
+
+pub struct PchScraper {
+    pub mode: CrawlMode
+}
+
+#[async_trait]
+impl Scraper for PchScraper {
+    /// `scrape` implementation for Packet Clearing House (PCH) route collectors.
+    ///
+    /// Each PCH collector publishes its daily MRT dumps in `YYYY/MM` directories, e.g.
+    /// https://www.pch.net/resources/Raw_Routing_Data/route-collector.fra.pch.net/2022/11/
+    async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
+        info!("scraping PCH collector {}; mode = {}", collector.id, &self.mode);
+
+        let months = match self.mode {
+            CrawlMode::Latest | CrawlMode::TwoMonths | CrawlMode::Range {..} => {
+                self.mode.months()
+            }
+            CrawlMode::Bootstrap => {
+                let year_link_pattern: Regex = Regex::new(r#"<a href="(\d{4})/">.*"#).unwrap();
+                let month_link_pattern: Regex = Regex::new(r#"<a href="(\d{2})/">.*"#).unwrap();
+                let body = fetcher().get_text(collector.url.as_str()).await?;
+                let years: Vec<i32> = year_link_pattern.captures_iter(body.as_str())
+                    .filter_map(|cap| cap[1].parse::<i32>().ok())
+                    .collect();
+
+                let mut res = vec![];
+                for year in years {
+                    let body = fetcher().get_text(format!("{}/{}", collector.url, year).as_str()).await?;
+                    for cap in month_link_pattern.captures_iter(body.as_str()) {
+                        let month = match cap[1].parse::<u32>() {
+                            Ok(m) => m,
+                            Err(_) => continue
+                        };
+                        if let Some(conn) = db {
+                            let month_str = format!("{}.{:02}", year, month);
+                            if conn.count_records_in_month(collector.id.as_str(), month_str.as_str()).await? > 0 {
+                                info!("skip month {} for {} in bootstrap mode", month_str.as_str(), collector.id.as_str());
+                                continue
+                            }
+                        }
+                        res.push((year, month));
+                    }
+                }
+                res
+            }
+        };
+
+        info!("total of {} months to scrape", months.len());
+
+        let stream = futures::stream::iter(months).map(|(year, month)| {
+            let url = format!("{}/{}/{:02}", collector.url, year, month);
+            self.scrape_month(url, format!("{}.{:02}", year, month), collector.id.clone(), db)
+        }).buffer_unordered(100);
+        collect_results(collector.id.as_str(), stream).await
+    }
+
+    fn expected_interval(&self, _data_type: DataType) -> Option<chrono::Duration> {
+        Some(chrono::Duration::seconds(PCH_UPDATE_INTERVAL))
+    }
+}
+
+impl PchScraper {
+    async fn scrape_month(&self, url: String, month: String, collector_id: String, db: Option<&DbConnection>) -> Result<Vec<Item>, ScrapeError> {
+        info!("scraping data for {} {} ...", collector_id.as_str(), &month);
+        let (body, validators) = match fetch_listing(self.mode, url.as_str(), db).await? {
+            Some(listing) => listing,
+            None => {
+                info!("    listing for {} {} not modified, skipped", collector_id.as_str(), &month);
+                return Ok(vec![])
+            }
+        };
+        info!("    download for {} {} finished ", collector_id.as_str(), &month);
+        let listing_url = url.clone();
+
+        let collector_clone = collector_id.clone();
+
+        let mut data_items: Vec<Item> =
+        tokio::task::spawn_blocking(move || {
+            let items = extract_link_size(body.as_str())?;
+            Ok::<_, ScrapeError>(items.iter().filter_map(|(link, size)| {
+                let file_url = format!("{}/{}", url, link);
+                parse_pch_item(file_url, *size, collector_id.as_str())
+            }).collect())
+        }).await??;
+
+        if let Some(conn) = db {
+            data_items = insert_new_items(self.mode, conn, collector_clone.as_str(), month.as_str(), data_items).await?;
+            if !validators.is_empty() {
+                conn.save_listing_validators(listing_url.as_str(), &validators).await?;
+            }
+        }
+
+        info!("scraping data for {} ... finished", &month);
+        Ok(data_items)
+    }
+}
+
+/// Convert a PCH file URL into an [Item], `None` if the file is not a recognized MRT dump.
+///
+/// PCH file names carry the collector name, the dump type and the date, with an optional time,
+/// e.g. `route-collector.fra.pch.net-mrt-bgp-updates-2022-11-01-00-00.gz`.
+fn parse_pch_item(url: String, size: i64, collector_id: &str) -> Option<Item> {
+    let file_pattern: Regex = Regex::new(
+        r#"(update|rib|table)s?[-_.](\d{4})[-.]?(\d{2})[-.]?(\d{2})(?:[-.](\d{2})[-.]?(\d{2}))?\.(gz|bz2)$"#
+    ).unwrap();
+    let file_name = url.rsplit('/').next()?;
+    let cap = file_pattern.captures(file_name)?;
+    let time_str = format!("{}{}{}.{}{}", &cap[2], &cap[3], &cap[4],
+                           cap.get(5).map(|m| m.as_str()).unwrap_or("00"),
+                           cap.get(6).map(|m| m.as_str()).unwrap_or("00"));
+    let ts_start = NaiveDateTime::parse_from_str(time_str.as_str(), "%Y%m%d.%H%M").ok()?;
+    let (data_type, ts_end) = match &cap[1] {
+        "update" => (DataType::BgpUpdate, ts_start + chrono::Duration::seconds(PCH_UPDATE_INTERVAL)),
+        _ => (DataType::BgpTableDump, ts_start),
+    };
+    Some(Item {
+        ts_start,
+        ts_end,
+        collector_id: collector_id.to_string(),
+        data_type,
+        url,
+        rough_size: size,
+        exact_size: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pch_item() {
+        let base = "https://www.pch.net/resources/Raw_Routing_Data/route-collector.fra.pch.net/2022/11";
+
+        let item = parse_pch_item(format!("{}/route-collector.fra.pch.net-mrt-bgp-updates-2022-11-01-00-00.gz", base), 1024, "route-collector.fra").unwrap();
+        assert_eq!(item.data_type, DataType::BgpUpdate);
+        assert_eq!(item.ts_start, NaiveDateTime::parse_from_str("2022-11-01 00:00", "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(item.ts_end, NaiveDateTime::parse_from_str("2022-11-02 00:00", "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(item.rough_size, 1024);
+
+        let item = parse_pch_item(format!("{}/route-collector.fra.pch.net-mrt-bgp-table-2022.11.02.gz", base), 1024, "route-collector.fra").unwrap();
+        assert_eq!(item.data_type, DataType::BgpTableDump);
+        assert_eq!(item.ts_start, NaiveDateTime::parse_from_str("2022-11-02 00:00", "%Y-%m-%d %H:%M").unwrap());
+        assert_eq!(item.ts_start, item.ts_end);
+
+        let item = parse_pch_item(format!("{}/route-collector.fra.pch.net-rib.20221103.1200.bz2", base), 1024, "route-collector.fra").unwrap();
+        assert_eq!(item.data_type, DataType::BgpTableDump);
+        assert_eq!(item.ts_start, NaiveDateTime::parse_from_str("2022-11-03 12:00", "%Y-%m-%d %H:%M").unwrap());
+
+        assert!(parse_pch_item(format!("{}/route-collector.fra.pch.net-ipv4_bgp_routes.2022.11.01.txt", base), 1024, "route-collector.fra").is_none());
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_scrape_fixtures() {
+        use crate::scrapers::fixtures::{month_aliases, scraped_months, serve};
+        use std::sync::atomic::Ordering;
+
+        // items in the recorded listings of each month, the 2022/10 listing also has a text routes
+        // file that is not an MRT dump
+        let recorded = |month: &str| match month {
+            "2022.10" => 5,
+            _ => 3,
+        };
+        let modes = [
+            CrawlMode::Bootstrap,
+            CrawlMode::Range { from: (2022, 11), to: (2022, 11) },
+            CrawlMode::Latest,
+            CrawlMode::TwoMonths,
+        ];
+        for mode in modes {
+            let server = serve("pch", month_aliases(mode)).await;
+            let collector = Collector {
+                id: "route-collector.fra.pch.net".to_string(),
+                project: Project::Pch,
+                url: format!("{}/route-collector.fra.pch.net", server.url),
+                meta: Default::default()
+            };
+            let db = DbConnection::new("sqlite::memory:").await.unwrap();
+            db.insert_collectors(std::slice::from_ref(&collector)).await.unwrap();
+            let scraper = PchScraper { mode };
+            let months = scraped_months(mode);
+            let total = months.iter().map(|(_, recorded_month)| recorded(recorded_month)).sum::<i64>();
+
+            scraper.scrape(&collector, Some(&db)).await.unwrap();
+            assert_eq!(db.search_items(&ItemFilter::default(), 1, 100).await.unwrap().len() as i64, total, "{}", mode);
+
+            // the latest month, served as `YYYY/MM` with `YYYY-MM-DD` and `YYYY.MM.DD` file names
+            let (served, recorded_month) = months.iter().max().unwrap();
+            assert_eq!(db.count_records_in_month(collector.id.as_str(), served).await.unwrap(), recorded(recorded_month), "{}", mode);
+            let month_start = NaiveDateTime::parse_from_str(format!("{}.01 00:00", served).as_str(), "%Y.%m.%d %H:%M").unwrap();
+            let filter = ItemFilter { ts_start: Some(month_start), data_type: Some(DataType::BgpUpdate), ..Default::default() };
+            let updates = db.search_items(&filter, 1, 10).await.unwrap();
+            assert_eq!(updates.len(), 2, "{}", mode);
+            assert_eq!(updates[0].url, format!("{}/{}/route-collector.fra.pch.net-mrt-bgp-updates-{}-01-00-00.gz",
+                                               collector.url, served.replace('.', "/"), served.replace('.', "-")));
+            assert_eq!(updates[0].ts_start, month_start);
+            assert_eq!(updates[0].ts_end - updates[0].ts_start, chrono::Duration::days(1));
+            assert_eq!(updates[0].rough_size, (4.1 * 1024.0 * 1024.0) as i64);
+            let filter = ItemFilter { ts_start: Some(month_start), data_type: Some(DataType::BgpTableDump), ..Default::default() };
+            let dumps = db.search_items(&filter, 1, 10).await.unwrap();
+            assert_eq!(dumps.len(), 1, "{}", mode);
+            assert_eq!(dumps[0].ts_start, month_start);
+            assert_eq!(dumps[0].rough_size, 40 * 1024 * 1024);
+
+            // a second run adds nothing, with unchanged listings skipped by conditional requests
+            scraper.scrape(&collector, Some(&db)).await.unwrap();
+            assert_eq!(db.search_items(&ItemFilter::default(), 1, 100).await.unwrap().len() as i64, total, "{}", mode);
+            if matches!(mode, CrawlMode::Latest | CrawlMode::TwoMonths) {
+                assert_eq!(server.not_modified.load(Ordering::SeqCst), months.len(), "{}", mode);
+            }
+        }
+    }
+}
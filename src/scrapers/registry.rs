@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use crate::scrapers::{ArchiveScraper, CrawlMode, PchScraper, RipeRisScraper, RouteViewsScraper, Scraper};
+
+/// Function that builds a [Scraper] for a given crawl mode.
+pub type ScraperBuilder = Box<dyn Fn(CrawlMode) -> Box<dyn Scraper> + Send + Sync>;
+
+/// Registry of scrapers keyed by project name.
+///
+/// The default registry contains the scrapers shipped with this crate (`routeviews`, `riperis`,
+/// `pch` and the archived `isolario`). Downstream crates can register additional projects:
+///
+/// ```no_run
+/// use bgpkit_broker_backend::scrapers::{RouteViewsScraper, ScraperRegistry};
+///
+/// let mut registry = ScraperRegistry::default();
+/// registry.register("my-mirror", |mode| Box::new(RouteViewsScraper{ mode }));
+/// ```
+///
+/// and run the updater with them through [crate::updater::main].
+pub struct ScraperRegistry {
+    builders: HashMap<String, ScraperBuilder>,
+}
+
+impl ScraperRegistry {
+    /// Create an empty registry.
+    pub fn new() -> ScraperRegistry {
+        ScraperRegistry{ builders: HashMap::new() }
+    }
+
+    /// Register a scraper builder for a project, replacing any previously registered one.
+    pub fn register<F>(&mut self, project: &str, builder: F)
+        where F: Fn(CrawlMode) -> Box<dyn Scraper> + Send + Sync + 'static
+    {
+        self.builders.insert(project.to_string(), Box::new(builder));
+    }
+
+    /// Check if a scraper is registered for a project.
+    pub fn contains(&self, project: &str) -> bool {
+        self.builders.contains_key(project)
+    }
+
+    /// Names of all registered projects.
+    pub fn projects(&self) -> Vec<&str> {
+        let mut projects = self.builders.keys().map(|p| p.as_str()).collect::<Vec<&str>>();
+        projects.sort_unstable();
+        projects
+    }
+
+    /// Build the scraper for a project, `None` if no scraper is registered for it.
+    pub fn build(&self, project: &str, mode: CrawlMode) -> Option<Box<dyn Scraper>> {
+        self.builders.get(project).map(|builder| builder(mode))
+    }
+}
+
+impl Default for ScraperRegistry {
+    fn default() -> Self {
+        let mut registry = ScraperRegistry::new();
+        registry.register("routeviews", |mode| Box::new(RouteViewsScraper{ mode }));
+        registry.register("riperis", |mode| Box::new(RipeRisScraper{ mode }));
+        registry.register("pch", |mode| Box::new(PchScraper{ mode }));
+        registry.register("isolario", |mode| Box::new(ArchiveScraper{ mode, rib_interval: 2*60*60, update_interval: 5*60 }));
+        registry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        let mut registry = ScraperRegistry::default();
+        assert_eq!(registry.projects(), vec!["isolario", "pch", "riperis", "routeviews"]);
+        assert!(registry.build("my-mirror", CrawlMode::Latest).is_none());
+
+        registry.register("my-mirror", |mode| Box::new(RouteViewsScraper{ mode }));
+        assert!(registry.contains("my-mirror"));
+        assert!(registry.build("my-mirror", CrawlMode::Latest).is_some());
+    }
+}
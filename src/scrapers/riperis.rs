@@ -1,52 +1,37 @@
-use chrono::{Datelike, Utc};
 use crate::scrapers::*;
 use log::info;
 use futures::StreamExt;
 use tokio;
-use crate::scrapers::utils::shift_months;
+
+/// RIPE RIS publishes one table dump every 8 hours.
+const RIB_INTERVAL: i64 = 8*60*60;
+/// RIPE RIS publishes one updates file every 5 minutes.
+const UPDATE_INTERVAL: i64 = 5*60;
 
 pub struct RipeRisScraper {
     pub mode: CrawlMode
 }
 
-impl RipeRisScraper {
+#[async_trait]
+impl Scraper for RipeRisScraper {
     /// `scrape` implementation for RIPE RIS.
-    pub async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
-        info!("scraping RIPE RIS collector {}; only latest month = {}", collector.id, &self.mode);
+    async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
+        info!("scraping RIPE RIS collector {}; mode = {}", collector.id, &self.mode);
 
         let months = match self.mode {
-            CrawlMode::Latest => {
-                let ts = Utc::now();
-                let ts2 = ts - chrono::Duration::days(1);
-                if ts.month() == ts2.month() {
-                    vec![
-                        format!("{}.{:02}",ts.year(), ts.month())
-                    ]
-                } else {
-                    // on borderline date, i.e. on the end of a month
-                    // we check both current and previous month to make sure we don't miss anything
-                    vec![
-                        format!("{}.{:02}",ts2.year(), ts2.month()),
-                        format!("{}.{:02}",ts.year(), ts.month())
-                    ]
-                }
-            }
-            CrawlMode::TwoMonths => {
-                let ts = Utc::now();
-                let ts2 = shift_months(ts, -1);
-                vec![
-                    format!("{}.{:02}",ts2.year(), ts2.month()),
-                    format!("{}.{:02}",ts.year(), ts.month())
-                ]
+            CrawlMode::Latest | CrawlMode::TwoMonths | CrawlMode::Range {..} => {
+                self.mode.months().into_iter()
+                    .map(|(year, month)| format!("{}.{:02}", year, month))
+                    .collect()
             }
             CrawlMode::Bootstrap => {
                 let month_link_pattern: Regex = Regex::new(r#"<a href="(....\...)/">.*"#).unwrap();
-                let body = reqwest::get(collector.url.as_str()).await?.text().await?;
+                let body = fetcher().get_text(collector.url.as_str()).await?;
                 let mut res = vec![];
                 for cap in month_link_pattern.captures_iter(body.as_str()) {
                     let month = cap[1].to_owned();
                     if let Some(conn) = db {
-                        if conn.count_records_in_month(collector.id.as_str(), month.as_str()).await > 0 {
+                        if conn.count_records_in_month(collector.id.as_str(), month.as_str()).await? > 0 {
                             info!("skip month {} for {} in bootstrap mode", month.as_str(), collector.id.as_str());
                             continue
                         }
@@ -59,7 +44,7 @@ impl RipeRisScraper {
 
         info!("total of {} months to scrape", months.len());
 
-        let mut stream = futures::stream::iter(months.clone()).map(|month| {
+        let stream = futures::stream::iter(months).map(|month| {
             let url = format!("{}/{}", collector.url, month);
             self.scrape_month(
                 url,
@@ -68,73 +53,91 @@ impl RipeRisScraper {
                 db,
             )
         }).buffer_unordered(100);
-        while let Some(_res) = stream.next().await {
+        collect_results(collector.id.as_str(), stream).await
+    }
+
+    fn expected_interval(&self, data_type: DataType) -> Option<chrono::Duration> {
+        match data_type {
+            DataType::BgpTableDump => Some(chrono::Duration::seconds(RIB_INTERVAL)),
+            DataType::BgpUpdate => Some(chrono::Duration::seconds(UPDATE_INTERVAL)),
         }
+    }
 
-        Ok( () )
+    /// Collectors are the `rrcNN` directories of the data root, e.g. https://data.ris.ripe.net/rrc00.
+    async fn discover(&self, project: &Project, root_url: &str) -> Result<Vec<Collector>, ScrapeError> {
+        let root_url = root_url.trim_end_matches('/');
+        let body = fetcher().get_text(format!("{}/", root_url).as_str()).await?;
+        let collector_pattern: Regex = Regex::new(r#"^rrc\d+$"#).unwrap();
+        Ok(extract_dir_links(body.as_str()).into_iter()
+            .filter(|dir| collector_pattern.is_match(dir.as_str()))
+            .map(|dir| Collector{
+                url: format!("{}/{}", root_url, dir),
+                id: dir,
+                project: project.clone(),
+                meta: Default::default()
+            })
+            .collect())
     }
+}
+
+impl RipeRisScraper {
 
     async fn scrape_month(&self, url: String, month: String, collector_id: String, db: Option<&DbConnection>) -> Result<Vec<Item>, ScrapeError>{
         info!("scraping data for {} {} ...", collector_id.as_str(), &month);
-        let body = reqwest::get(url.clone()).await?.text().await?;
+        let (body, validators) = match fetch_listing(self.mode, url.as_str(), db).await? {
+            Some(listing) => listing,
+            None => {
+                info!("    listing for {} {} not modified, skipped", collector_id.as_str(), &month);
+                return Ok(vec![])
+            }
+        };
         info!("    download for {} {} finished ", collector_id.as_str(), &month);
+        let listing_url = url.clone();
 
         let collector_clone = collector_id.clone();
 
         let mut data_items: Vec<Item> =
         tokio::task::spawn_blocking(move || {
-            let items = extract_link_size(body.as_str());
+            let items = extract_link_size(body.as_str())?;
+            let updates_link_pattern: Regex = Regex::new(r#".*(........\.....)\.gz.*"#).unwrap();
             items.iter().map(|(link, size)|{
                 let url = match url.contains("https") {
                     true => format!("{}/{}",url, link),
                     false => format!("{}/{}",url, link).replace("http", "https")
                 };
-                let updates_link_pattern: Regex = Regex::new(r#".*(........\.....)\.gz.*"#).unwrap();
-                let time_str = updates_link_pattern.captures(&url).unwrap().get(1).unwrap().as_str();
-                let unix_time = NaiveDateTime::parse_from_str(time_str, "%Y%m%d.%H%M").unwrap();
-                match link.contains("update") {
+                let time_str = match updates_link_pattern.captures(&url) {
+                    Some(cap) => cap[1].to_string(),
+                    None => return Err(ScrapeError::ParseError(format!("no timestamp in file name {}", url)))
+                };
+                let unix_time = NaiveDateTime::parse_from_str(time_str.as_str(), "%Y%m%d.%H%M")?;
+                Ok(match link.contains("update") {
                     true => Item {
                         ts_start: unix_time,
-                        ts_end: unix_time + chrono::Duration::seconds(5*60),
+                        ts_end: unix_time + chrono::Duration::seconds(UPDATE_INTERVAL),
                         url: url.clone(),
-                        rough_size: size.clone(),
+                        rough_size: *size,
                         exact_size: 0,
                         collector_id: collector_id.clone(),
-                        data_type: "update".to_string(),
+                        data_type: DataType::BgpUpdate,
                     },
                     false => Item {
                         ts_start: unix_time,
                         ts_end: unix_time,
                         url: url.clone(),
-                        rough_size: size.clone(),
+                        rough_size: *size,
                         exact_size: 0,
                         collector_id: collector_id.clone(),
-                        data_type: "rib".to_string(),
+                        data_type: DataType::BgpTableDump,
                     }
-                }
-            }).collect()
-        }).await.unwrap();
+                })
+            }).collect::<Result<Vec<Item>, ScrapeError>>()
+        }).await??;
 
         if let Some(conn) = db {
-            info!("    insert to db for {} {}...", collector_clone.as_str(), &month);
-
-            let to_insert = match self.mode {
-                CrawlMode::Latest | CrawlMode::TwoMonths => {
-                    let current_month_items = conn.get_urls_in_month(collector_clone.as_str(), month.as_str()).await;
-                    data_items.into_iter().filter(|x|!current_month_items.contains(&x.url))
-                        .collect::<Vec<Item>>()
-                }
-                CrawlMode::Bootstrap => {
-                    data_items
-                }
-            };
-
-            data_items = conn.insert_items(&to_insert).await;
-
-            #[cfg(feature = "kafka")]
-            conn.notify(&data_items).await;
-
-            info!("    insert to db for {} {}... {}/{} inserted", collector_clone.as_str(), &month, to_insert.len(), data_items.len());
+            data_items = insert_new_items(self.mode, conn, collector_clone.as_str(), month.as_str(), data_items).await?;
+            if !validators.is_empty() {
+                conn.save_listing_validators(listing_url.as_str(), &validators).await?;
+            }
         }
 
         info!("scraping data for {} ... finished", &month);
@@ -147,15 +150,16 @@ mod tests {
     use super::*;
 
     #[tokio::test]
+    #[ignore = "requires network access"]
     async fn test_parsing_new_ris() {
         let scraper = RipeRisScraper{ mode: CrawlMode::Latest };
         let mut res = scraper.scrape_month("https://test-data.ris.ripe.net/rrc00/2001.01/".to_string(), "2001.01".to_string(), "rrc00".to_string(), None).await.unwrap();
-        res.sort_by_key(|item| (item.data_type.clone(), item.ts_start.clone()));
+        res.sort_by_key(|item| (item.data_type, item.ts_start));
         dbg!(&res[0]);
 
         let scraper = RipeRisScraper{ mode: CrawlMode::Latest };
         let mut res2 = scraper.scrape_month("https://data.ris.ripe.net/rrc00/2001.01/".to_string(), "2001.01".to_string(), "rrc00".to_string(), None).await.unwrap();
-        res2.sort_by_key(|item| (item.data_type.clone(), item.ts_start.clone()));
+        res2.sort_by_key(|item| (item.data_type, item.ts_start));
         dbg!(&res2[0]);
 
         assert_eq!(res.len(), res2.len());
@@ -170,12 +174,12 @@ mod tests {
 
         let scraper = RipeRisScraper{ mode: CrawlMode::Latest };
         let mut res = scraper.scrape_month("http://test-data.ris.ripe.net/rrc00/2022.01/".to_string(), "2022.01".to_string(), "rrc00".to_string(), None).await.unwrap();
-        res.sort_by_key(|item| (item.data_type.clone(), item.ts_start.clone()));
+        res.sort_by_key(|item| (item.data_type, item.ts_start));
         dbg!(&res[0]);
 
         let scraper = RipeRisScraper{ mode: CrawlMode::Latest };
         let mut res2 = scraper.scrape_month("http://data.ris.ripe.net/rrc00/2022.01/".to_string(), "2022.01".to_string(), "rrc00".to_string(), None).await.unwrap();
-        res2.sort_by_key(|item| (item.data_type.clone(), item.ts_start.clone()));
+        res2.sort_by_key(|item| (item.data_type, item.ts_start));
         dbg!(&res2[0]);
 
         assert_eq!(res.len(), res2.len());
@@ -188,4 +192,69 @@ mod tests {
             // assert_eq!(res[i].rough_size, res2[i].rough_size);
         }
     }
+
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_scrape_fixtures() {
+        use crate::scrapers::fixtures::{month_aliases, scraped_months, serve};
+        use std::sync::atomic::Ordering;
+
+        // items in the recorded listings of each month, rrc00 in the current listing format and
+        // rrc01 in the old one
+        let recorded = |collector: &str, month: &str| match (collector, month) {
+            ("rrc00", "2022.10") => 5,
+            ("rrc00", _) => 3,
+            (_, "2022.10") => 4,
+            _ => 2,
+        };
+        let modes = [
+            CrawlMode::Bootstrap,
+            CrawlMode::Range { from: (2022, 10), to: (2022, 10) },
+            CrawlMode::Latest,
+            CrawlMode::TwoMonths,
+        ];
+        for mode in modes {
+            let server = serve("riperis", month_aliases(mode)).await;
+            let collectors = ["rrc00", "rrc01"].iter().map(|id| Collector{
+                id: id.to_string(),
+                project: Project::RipeRis,
+                url: format!("{}/{}", server.url, id),
+                meta: Default::default()
+            }).collect::<Vec<Collector>>();
+            let db = DbConnection::new("sqlite::memory:").await.unwrap();
+            db.insert_collectors(&collectors).await.unwrap();
+            let scraper = RipeRisScraper{ mode };
+            let months = scraped_months(mode);
+
+            for collector in &collectors {
+                scraper.scrape(collector, Some(&db)).await.unwrap();
+                for (served, recorded_month) in &months {
+                    assert_eq!(db.count_records_in_month(collector.id.as_str(), served).await.unwrap(),
+                               recorded(collector.id.as_str(), recorded_month), "{} {} {}", mode, collector.id, served);
+                }
+            }
+            if let CrawlMode::Range {..} = mode {
+                for collector in &collectors {
+                    let filter = ItemFilter { collector_ids: vec![collector.id.clone()], data_type: Some(DataType::BgpUpdate), ..Default::default() };
+                    let update = &db.search_items(&filter, 1, 1).await.unwrap()[0];
+                    // listed files are linked over HTTPS
+                    assert_eq!(update.url, format!("{}/2022.10/updates.20221001.0000.gz", collector.url).replace("http", "https"));
+                    assert_eq!(update.ts_end - update.ts_start, chrono::Duration::minutes(5));
+                    assert_eq!(update.rough_size, (6.4 * 1024.0 * 1024.0) as i64);
+                }
+            }
+
+            // a second run adds nothing, with unchanged listings skipped by conditional requests
+            for collector in &collectors {
+                scraper.scrape(collector, Some(&db)).await.unwrap();
+            }
+            let total = collectors.iter()
+                .map(|c| months.iter().map(|(_, m)| recorded(c.id.as_str(), m)).sum::<i64>())
+                .sum::<i64>();
+            assert_eq!(db.search_items(&ItemFilter::default(), 1, 100).await.unwrap().len() as i64, total, "{}", mode);
+            if matches!(mode, CrawlMode::Latest | CrawlMode::TwoMonths) {
+                assert_eq!(server.not_modified.load(Ordering::SeqCst), collectors.len() * months.len(), "{}", mode);
+            }
+        }
+    }
 }
@@ -1,54 +1,39 @@
-use chrono::{Datelike, Utc};
 use crate::scrapers::*;
 use log::info;
 use futures::StreamExt;
-use crate::scrapers::utils::shift_months;
+
+/// RouteViews publishes one table dump every 2 hours.
+const RIB_INTERVAL: i64 = 2*60*60;
+/// RouteViews publishes one updates file every 15 minutes.
+const UPDATE_INTERVAL: i64 = 15*60;
 
 pub struct RouteViewsScraper{
     pub mode: CrawlMode,
 }
 
-impl RouteViewsScraper {
+#[async_trait]
+impl Scraper for RouteViewsScraper {
 
     /// `scrape` implementation for RouteViews.
     ///
     /// Example of RouteViews2: http://archive.routeviews.org/bgpdata/
-    pub async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
-        info!("scraping RouteViews collector {}; only latest month = {}", collector.id, &self.mode);
+    async fn scrape(&self, collector: &Collector, db: Option<&DbConnection>) -> Result<(), ScrapeError> {
+        info!("scraping RouteViews collector {}; mode = {}", collector.id, &self.mode);
 
         let months = match &self.mode {
-            CrawlMode::Latest => {
-                let ts = Utc::now();
-                let ts2 = ts - chrono::Duration::days(1);
-                if ts.month() == ts2.month() {
-                    vec![
-                        format!("{}.{:02}", ts.year(), ts.month())
-                    ]
-                } else {
-                    // on borderline date, i.e. on the end of a month
-                    // we check both current and previous month to make sure we don't miss anything
-                    vec![
-                        format!("{}.{:02}", ts2.year(), ts2.month()),
-                        format!("{}.{:02}", ts.year(), ts.month())
-                    ]
-                }
-            }
-            CrawlMode::TwoMonths => {
-                let ts = Utc::now();
-                let ts2 = shift_months(ts, -1);
-                vec![
-                    format!("{}.{:02}", ts2.year(), ts2.month()),
-                    format!("{}.{:02}", ts.year(), ts.month())
-                ]
+            CrawlMode::Latest | CrawlMode::TwoMonths | CrawlMode::Range {..} => {
+                self.mode.months().into_iter()
+                    .map(|(year, month)| format!("{}.{:02}", year, month))
+                    .collect()
             }
             CrawlMode::Bootstrap => {
                     let month_link_pattern: Regex = Regex::new(r#"<a href="(....\...)/">.*"#).unwrap();
-                    let body = reqwest::get(collector.url.as_str()).await?.text().await?;
+                    let body = fetcher().get_text(collector.url.as_str()).await?;
                     let mut res = vec![];
                     for cap in month_link_pattern.captures_iter(body.as_str()) {
                         let month = cap[1].to_owned();
                         if let Some(conn) = db {
-                            if conn.count_records_in_month(collector.id.as_str(), month.as_str()).await > 0 {
+                            if conn.count_records_in_month(collector.id.as_str(), month.as_str()).await? > 0 {
                                 info!("skip month {} for {} in bootstrap mode", month.as_str(), collector.id.as_str());
                                 continue
                             }
@@ -61,75 +46,96 @@ impl RouteViewsScraper {
 
         info!("total of {} months to scrape", months.len());
 
-        let mut stream = futures::stream::iter(months.clone()).map(|month| {
+        let stream = futures::stream::iter(months.clone()).map(|month| {
             let ribs_url = format!("{}/{}/RIBS", collector.url, month);
-            self.scrape_items(ribs_url, month, "rib".to_string(), collector.id.clone(), db)
+            self.scrape_items(ribs_url, month, DataType::BgpTableDump, collector.id.clone(), db)
         }).buffer_unordered(100);
-        while let Some(_res) = stream.next().await { }
+        let ribs = collect_results(collector.id.as_str(), stream).await;
 
-        let mut stream = futures::stream::iter(months).map(|month| {
+        let stream = futures::stream::iter(months).map(|month| {
             let updates_url = format!("{}/{}/UPDATES", collector.url, month);
-            self.scrape_items(updates_url, month, "update".to_string(), collector.id.clone(), db)
+            self.scrape_items(updates_url, month, DataType::BgpUpdate, collector.id.clone(), db)
         }).buffer_unordered(100);
-        while let Some(_res) = stream.next().await {  }
+        let updates = collect_results(collector.id.as_str(), stream).await;
 
-        Ok( () )
+        ribs.and(updates)
     }
 
-    async fn scrape_items(&self, url: String, month: String, data_type_str: String, collector_id: String, db: Option<&DbConnection>) -> Result<(), ScrapeError>{
-        info!("scraping data for {} {}-{} ... ", collector_id.as_str(), &month, &data_type_str);
-        let body = reqwest::get(url.clone()).await?.text().await?;
-        info!("    download for {} {}-{} finished ", collector_id.as_str(), &month, &data_type_str);
+    fn expected_interval(&self, data_type: DataType) -> Option<chrono::Duration> {
+        match data_type {
+            DataType::BgpTableDump => Some(chrono::Duration::seconds(RIB_INTERVAL)),
+            DataType::BgpUpdate => Some(chrono::Duration::seconds(UPDATE_INTERVAL)),
+        }
+    }
+
+    /// Collectors are the `route-views*` directories of the archive root, e.g.
+    /// http://archive.routeviews.org/route-views3/bgpdata, except for route-views2 which publishes
+    /// to `bgpdata` under the root directly.
+    async fn discover(&self, project: &Project, root_url: &str) -> Result<Vec<Collector>, ScrapeError> {
+        let root_url = root_url.trim_end_matches('/');
+        let body = fetcher().get_text(format!("{}/", root_url).as_str()).await?;
+        let collector_pattern: Regex = Regex::new(r#"^route-views[\w.-]*$"#).unwrap();
+        Ok(extract_dir_links(body.as_str()).into_iter().filter_map(|dir| {
+            let (id, url) = match dir.as_str() {
+                "bgpdata" => ("route-views2".to_string(), format!("{}/bgpdata", root_url)),
+                _ if collector_pattern.is_match(dir.as_str()) => (dir.clone(), format!("{}/{}/bgpdata", root_url, dir)),
+                _ => return None
+            };
+            Some(Collector{ id, project: project.clone(), url, meta: Default::default() })
+        }).collect())
+    }
+}
+
+impl RouteViewsScraper {
+
+    async fn scrape_items(&self, url: String, month: String, data_type: DataType, collector_id: String, db: Option<&DbConnection>) -> Result<(), ScrapeError>{
+        info!("scraping data for {} {}-{} ... ", collector_id.as_str(), &month, data_type);
+        let (body, validators) = match fetch_listing(self.mode, url.as_str(), db).await? {
+            Some(listing) => listing,
+            None => {
+                info!("    listing for {} {}-{} not modified, skipped", collector_id.as_str(), &month, data_type);
+                return Ok(())
+            }
+        };
+        info!("    download for {} {}-{} finished ", collector_id.as_str(), &month, data_type);
+        let listing_url = url.clone();
 
         let collector_clone = collector_id.clone();
+        let interval = match data_type {
+            DataType::BgpTableDump => chrono::Duration::seconds(0),
+            DataType::BgpUpdate => chrono::Duration::seconds(UPDATE_INTERVAL),
+        };
 
         let data_items: Vec<Item> =
         tokio::task::spawn_blocking(move || {
-            let items = extract_link_size(body.as_str());
+            let items = extract_link_size(body.as_str())?;
+            let updates_link_pattern: Regex = Regex::new(r#".*(........\.....)\.bz2.*"#).unwrap();
             items.iter().map(|(link, size)| {
                 // http://archive.routeviews.org/bgpdata/2001.11/UPDATES/updates.20011101.0923.bz2
                 let url = format!("{}/{}", &url, link);
-                let updates_link_pattern: Regex = Regex::new(r#".*(........\.....)\.bz2.*"#).unwrap();
-                let time_str = updates_link_pattern.captures(&url).unwrap().get(1).unwrap().as_str();
-                let unix_time = NaiveDateTime::parse_from_str(time_str, "%Y%m%d.%H%M").unwrap();
-                let interval = match data_type_str.as_str(){
-                    "rib" => chrono::Duration::seconds(0),
-                    "update" => chrono::Duration::seconds(15*60),
-                    _ => panic!("unknown data type {}", data_type_str.as_str())
+                let time_str = match updates_link_pattern.captures(&url) {
+                    Some(cap) => cap[1].to_string(),
+                    None => return Err(ScrapeError::ParseError(format!("no timestamp in file name {}", url)))
                 };
+                let unix_time = NaiveDateTime::parse_from_str(time_str.as_str(), "%Y%m%d.%H%M")?;
 
-                Item {
+                Ok(Item {
                     ts_start: unix_time,
                     ts_end: unix_time+interval,
                     rough_size: *size,
                     exact_size: 0,
                     collector_id: collector_id.clone(),
-                    data_type: data_type_str.clone(),
+                    data_type,
                     url,
-                }
-            }).collect()
-        }).await.unwrap();
+                })
+            }).collect::<Result<Vec<Item>, ScrapeError>>()
+        }).await??;
 
         if let Some(conn) = db {
-            info!("    insert to db for {} {}...", collector_clone.as_str(), &month);
-
-            let to_insert = match self.mode {
-                CrawlMode::Latest | CrawlMode::TwoMonths => {
-                    let current_month_items = conn.get_urls_in_month(collector_clone.as_str(), month.as_str()).await;
-                    data_items.into_iter().filter(|x|!current_month_items.contains(&x.url))
-                        .collect::<Vec<Item>>()
-                }
-                CrawlMode::Bootstrap => {
-                    data_items
-                }
-            };
-
-            let inserted = conn.insert_items(&to_insert).await;
-
-            #[cfg(feature = "kafka")]
-            conn.notify(&inserted).await;
-
-            info!("    insert to db for {} {}... {}/{} inserted", collector_clone.as_str(), &month, to_insert.len(), inserted.len());
+            insert_new_items(self.mode, conn, collector_clone.as_str(), month.as_str(), data_items).await?;
+            if !validators.is_empty() {
+                conn.save_listing_validators(listing_url.as_str(), &validators).await?;
+            }
         }
 
 
@@ -144,23 +150,76 @@ mod tests {
 
 
     #[tokio::test]
+    #[ignore = "requires network access"]
     async fn test_routeviews() {
         env_logger::init();
 
         let _rv_collector = Collector{
             id: "rv2".to_string(),
-            project: "routeviews".to_string(),
-            url: "http://archive.routeviews.org/bgpdata".to_string()
+            project: Project::RouteViews,
+            url: "http://archive.routeviews.org/bgpdata".to_string(),
+            meta: Default::default()
         };
         let rv_scraper = RouteViewsScraper{ mode: CrawlMode::Latest };
         // let _ = rv_scraper.scrape(&rv_collector, true, None).await;
         rv_scraper.scrape_items(
             "http://archive.routeviews.org/route-views.linx/bgpdata/2014.03/RIBS".to_string(),
             "2004.03".to_string(),
-            "rib".to_string(),
+            DataType::BgpTableDump,
             "route-views.linx".to_string(),
             None
         ).await.unwrap();
     }
 
+    #[cfg(feature = "sqlite")]
+    #[tokio::test]
+    async fn test_scrape_fixtures() {
+        use crate::scrapers::fixtures::{month_aliases, scraped_months, serve};
+        use std::sync::atomic::Ordering;
+
+        // items in the recorded RIBS and UPDATES listings of each month
+        let recorded = |month: &str| match month {
+            "2022.10" => 7,
+            _ => 5,
+        };
+        let modes = [
+            CrawlMode::Bootstrap,
+            CrawlMode::Range { from: (2022, 10), to: (2022, 10) },
+            CrawlMode::Latest,
+            CrawlMode::TwoMonths,
+        ];
+        for mode in modes {
+            let server = serve("routeviews", month_aliases(mode)).await;
+            let collector = Collector{
+                id: "route-views.linx".to_string(),
+                project: Project::RouteViews,
+                url: format!("{}/route-views.linx/bgpdata", server.url),
+                meta: Default::default()
+            };
+            let db = DbConnection::new("sqlite::memory:").await.unwrap();
+            db.insert_collectors(std::slice::from_ref(&collector)).await.unwrap();
+            let scraper = RouteViewsScraper{ mode };
+
+            scraper.scrape(&collector, Some(&db)).await.unwrap();
+            let months = scraped_months(mode);
+            for (served, recorded_month) in &months {
+                assert_eq!(db.count_records_in_month(collector.id.as_str(), served).await.unwrap(), recorded(recorded_month), "{} {}", mode, served);
+            }
+            if let CrawlMode::Range {..} = mode {
+                let filter = ItemFilter { data_type: Some(DataType::BgpUpdate), ..Default::default() };
+                let update = &db.search_items(&filter, 1, 1).await.unwrap()[0];
+                assert_eq!(update.url, format!("{}/2022.10/UPDATES/updates.20221001.0000.bz2", collector.url));
+                assert_eq!(update.ts_end - update.ts_start, chrono::Duration::minutes(15));
+                assert_eq!(update.rough_size, 156 * 1024);
+            }
+
+            // a second run adds nothing, with unchanged listings skipped by conditional requests
+            scraper.scrape(&collector, Some(&db)).await.unwrap();
+            let total = months.iter().map(|(_, m)| recorded(m)).sum::<i64>();
+            assert_eq!(db.search_items(&ItemFilter::default(), 1, 100).await.unwrap().len() as i64, total, "{}", mode);
+            if matches!(mode, CrawlMode::Latest | CrawlMode::TwoMonths) {
+                assert_eq!(server.not_modified.load(Ordering::SeqCst), 2 * months.len(), "{}", mode);
+            }
+        }
+    }
 }
@@ -0,0 +1,137 @@
+//! Local HTTP server for tests of the HTTP clients.
+//!
+//! Every request is read in full, including a body announced by `Content-Length`, and answered
+//! by a handler closure. Handlers capture shared state to count or record the requests.
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+pub(crate) struct Request {
+    pub method: String,
+    /// request path including the query
+    pub path: String,
+    /// header lines with lowercase names
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Request {
+    /// Value of a header, by lowercase name.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+}
+
+pub(crate) struct Response {
+    /// status code and reason, e.g. `200 OK`
+    status: String,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: &str) -> Self {
+        Response { status: status.to_string(), headers: vec![], body: vec![] }
+    }
+
+    pub fn ok<B: Into<Vec<u8>>>(body: B) -> Self {
+        Response::new("200 OK").body(body)
+    }
+
+    pub fn not_found() -> Self {
+        Response::new("404 Not Found")
+    }
+
+    /// Serve `data` for a `Range: bytes=0-N` request with `206 Partial Content`, or in full
+    /// without a range.
+    pub fn range(request: &Request, data: &[u8]) -> Self {
+        let end = match request.header("range")
+            .and_then(|range| range.strip_prefix("bytes=0-"))
+            .and_then(|end| end.parse::<usize>().ok()) {
+            Some(end) => (end + 1).min(data.len()),
+            None => return Response::ok(data),
+        };
+        Response::new("206 Partial Content")
+            .header("Content-Range", format!("bytes 0-{}/{}", end - 1, data.len()).as_str())
+            .body(&data[..end])
+    }
+
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn body<B: Into<Vec<u8>>>(mut self, body: B) -> Self {
+        self.body = body.into();
+        self
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+        for (name, value) in &self.headers {
+            head.push_str(format!("{}: {}\r\n", name, value).as_str());
+        }
+        // a HEAD response sets its own Content-Length without a body
+        if !self.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("content-length")) {
+            head.push_str(format!("Content-Length: {}\r\n", self.body.len()).as_str());
+        }
+        head.push_str("Connection: close\r\n\r\n");
+        let mut bytes = head.into_bytes();
+        bytes.extend_from_slice(&self.body);
+        bytes
+    }
+}
+
+/// Read one request, `None` if the connection closed before a complete request head.
+async fn read_request(socket: &mut tokio::net::TcpStream) -> Option<Request> {
+    let mut data = vec![];
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.ok()?;
+        data.extend_from_slice(&buf[..n]);
+        let request = String::from_utf8_lossy(&data).to_string();
+        if let Some((head, body)) = request.split_once("\r\n\r\n") {
+            let mut lines = head.lines();
+            let mut request_line = lines.next()?.split_whitespace();
+            let method = request_line.next()?.to_string();
+            let path = request_line.next()?.to_string();
+            let headers = lines
+                .filter_map(|line| line.split_once(':'))
+                .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+                .collect::<Vec<(String, String)>>();
+            let length = headers.iter()
+                .find(|(name, _)| name == "content-length")
+                .and_then(|(_, value)| value.parse::<usize>().ok())
+                .unwrap_or(0);
+            if body.len() >= length || n == 0 {
+                return Some(Request { method, path, headers, body: body.to_string() })
+            }
+        }
+        if n == 0 {
+            return None
+        }
+    }
+}
+
+/// Serve every request with `handler`, returning the base URL of the server without a trailing
+/// slash.
+pub(crate) async fn serve<F>(handler: F) -> String
+where
+    F: Fn(Request) -> Response + Send + Sync + 'static,
+{
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handler = std::sync::Arc::new(handler);
+    tokio::spawn(async move {
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let handler = handler.clone();
+            tokio::spawn(async move {
+                if let Some(request) = read_request(&mut socket).await {
+                    let _ = socket.write_all(handler(request).to_bytes().as_slice()).await;
+                }
+            });
+        }
+    });
+    format!("http://{}", addr)
+}
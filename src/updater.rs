@@ -0,0 +1,666 @@
+//! The `bgpkit-broker-updater` command line, as a library so that downstream crates can run it
+//! with their own scrapers.
+//!
+//! A downstream binary registers its projects and hands the registry to [main]:
+//!
+//! ```no_run
+//! use bgpkit_broker_backend::scrapers::{RouteViewsScraper, ScraperRegistry};
+//!
+//! let mut registry = ScraperRegistry::default();
+//! registry.register("my-mirror", |mode| Box::new(RouteViewsScraper{ mode }));
+//! bgpkit_broker_backend::updater::main(registry);
+//! ```
+
+use std::time::Duration;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use log::{error, info, warn};
+use futures::{FutureExt, StreamExt};
+#[cfg(unix)]
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use crate::config::Config;
+use crate::db::{DbConnection, connect_pool, get_db_url, migrations};
+use crate::db::models::{Collector, DataType, ItemFilter, Project};
+use crate::dump;
+#[cfg(feature = "parquet")]
+use crate::export::export_items;
+use crate::db::webhook::WebhookNotifier;
+use crate::discovery::{discover_collectors, register_new_collectors, registered_collectors};
+use crate::errors::ScrapeError;
+use crate::fetch;
+use crate::metrics;
+use crate::metrics::metrics;
+use crate::gaps::{find_collector_gaps, gaps_to_csv};
+use crate::scrapers::{CrawlMode, ScraperRegistry};
+use crate::peers::index_collector_peers;
+use crate::verify::verify_collector;
+
+// command line options of the updater, parsed by [main]
+#[derive(Parser)]
+#[clap(name = "bgpkit-broker-updater")]
+pub struct Opts {
+    /// Collectors config file, required unless running the migrate, export, dump or load subcommands
+    #[clap(short, long)]
+    collectors_config: Option<String>,
+
+    /// Database URL string, this overwrites the DATABASE_URL env variable
+    #[clap(short, long)]
+    db_url: Option<String>,
+
+    /// Crawl mode: latest, two_months, bootstrap, range; required unless running a subcommand
+    #[clap(short, long, possible_values = ["latest", "two_months", "bootstrap", "range"])]
+    mode: Option<String>,
+
+    /// First month to crawl in range mode, in YYYY-MM format
+    #[clap(long, required_if_eq("mode", "range"))]
+    from: Option<String>,
+
+    /// Last month to crawl in range mode, in YYYY-MM format
+    #[clap(long, required_if_eq("mode", "range"))]
+    to: Option<String>,
+
+    /// Pretty print
+    #[clap(short, long)]
+    pretty: bool,
+
+    /// Verify files available and get file sizes
+    #[clap(short, long)]
+    verify: bool,
+
+    /// Maximum number of items to verify per collector, most recent items first
+    #[clap(long, default_value = "1000")]
+    verify_limit: i64,
+
+    /// Index the BGP peers of RIB dumps from their peer index tables
+    #[clap(long)]
+    index_peers: bool,
+
+    /// Maximum number of RIB dumps to index peers of per collector, most recent dumps first
+    #[clap(long, default_value = "100")]
+    index_peers_limit: i64,
+
+    /// Index wanted to scrape from, default to scrape from all collectors
+    #[clap(long)]
+    collector_id: Option<String>,
+
+    /// Kafka broker URL for new file notification
+    #[cfg(feature = "kafka")]
+    #[clap(long, env = "KAFKA_BROKER")]
+    kafka_broker: Option<String>,
+
+    /// Kafka topic for new file notification
+    #[cfg(feature = "kafka")]
+    #[clap(long, env = "KAFKA_TOPIC")]
+    kafka_topic: Option<String>,
+
+    /// Webhook URL to POST new files to, as JSON arrays of items
+    #[clap(long)]
+    webhook_url: Option<String>,
+
+    /// Secret to sign webhook bodies with HMAC-SHA256, sent in the X-Broker-Signature header
+    #[clap(long, requires = "webhook-url")]
+    webhook_secret: Option<String>,
+
+    /// Maximum number of items per webhook request
+    #[clap(long, default_value = "100")]
+    webhook_batch_size: usize,
+
+    /// Pushgateway URL to push metrics to at the end of each run
+    #[clap(long)]
+    pushgateway: Option<String>,
+
+    /// Job name the metrics are pushed under
+    #[clap(long, default_value = "bgpkit-broker-updater", requires = "pushgateway")]
+    metrics_job: String,
+
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum MigrateAction {
+    /// Apply all pending migrations
+    Up,
+    /// Revert the last applied migration
+    Down,
+    /// List the migrations and whether they are applied
+    Status,
+}
+
+/// Filters of the items to export or dump.
+#[derive(Args)]
+struct FilterArgs {
+    /// Only include files ending at or after this date, in YYYY-MM-DD format
+    #[clap(long)]
+    from: Option<NaiveDate>,
+
+    /// Only include files starting before this date, in YYYY-MM-DD format
+    #[clap(long)]
+    to: Option<NaiveDate>,
+
+    /// Only include files of this project, e.g. routeviews or riperis
+    #[clap(long)]
+    project: Option<String>,
+
+    /// Only include files of these collectors, comma-separated
+    #[clap(long, use_value_delimiter = true)]
+    collectors: Vec<String>,
+
+    /// Only include files of this data type
+    #[clap(long, possible_values = ["rib", "update"])]
+    data_type: Option<DataType>,
+}
+
+impl FilterArgs {
+    fn to_filter(&self) -> ItemFilter {
+        ItemFilter {
+            ts_start: self.from.and_then(|d| d.and_hms_opt(0, 0, 0)),
+            // the end date is exclusive
+            ts_end: self.to.and_then(|d| d.and_hms_opt(0, 0, 0)).map(|ts| ts - chrono::Duration::seconds(1)),
+            project: self.project.as_deref().map(Project::from),
+            collector_ids: self.collectors.clone(),
+            data_type: self.data_type,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the database schema
+    Migrate {
+        #[clap(subcommand)]
+        action: MigrateAction,
+    },
+    /// Report periods with missing files for each collector and data type
+    Gaps {
+        /// Start of the checked period, in YYYY-MM-DD format, default to seven days ago
+        #[clap(long)]
+        from: Option<NaiveDate>,
+
+        /// End of the checked period (exclusive), in YYYY-MM-DD format, default to now
+        #[clap(long)]
+        to: Option<NaiveDate>,
+
+        /// Output format: json or csv
+        #[clap(long, default_value = "json", possible_values = ["json", "csv"])]
+        format: String,
+
+        /// Output file, default to stdout
+        #[clap(short, long)]
+        output: Option<String>,
+    },
+    /// Export the index to Parquet files partitioned by project, year and month
+    #[cfg(feature = "parquet")]
+    Export {
+        /// Output directory of the partitions
+        #[clap(short, long)]
+        output_dir: String,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Dump collectors and items as newline-delimited JSON
+    Dump {
+        /// Output file, default to stdout
+        #[clap(short, long)]
+        output: Option<String>,
+
+        #[clap(flatten)]
+        filter: FilterArgs,
+    },
+    /// Load a dump created by the dump subcommand, skipping items already in the database
+    Load {
+        /// Input file, default to stdin
+        #[clap(short, long)]
+        input: Option<String>,
+    },
+    /// Discover collectors from the root pages of projects with a discovery_url, reporting new
+    /// collectors and configured collectors no longer found upstream
+    Discover {
+        /// Register the new collectors in the database, so that later runs and the next run of a
+        /// running daemon scrape them along with the configured ones
+        #[clap(long)]
+        register: bool,
+    },
+    /// Keep running, scraping the latest files on an interval and sweeping the last two months
+    /// periodically, until SIGTERM or SIGINT
+    Daemon {
+        /// Seconds between scrapes of the latest files
+        #[clap(long, default_value = "300")]
+        latest_interval: u64,
+
+        /// Seconds between sweeps of the last two months, the first one runs at startup
+        #[clap(long, default_value = "86400")]
+        sweep_interval: u64,
+    },
+}
+
+async fn run_scraper(c: &Collector, mode: CrawlMode, registry: &ScraperRegistry, conn: &DbConnection, verify_limit: Option<i64>, peers_limit: Option<i64>) -> Result<(), ScrapeError> {
+    let scraper = registry.build(c.project.as_str(), mode)
+        .ok_or_else(|| ScrapeError::ConfigError(format!("no scraper registered for project {}", c.project)))?;
+    scraper.scrape(c, Some(conn)).await?;
+    if let Some(limit) = verify_limit {
+        verify_collector(c.id.as_str(), conn, limit).await?;
+    }
+    if let Some(limit) = peers_limit {
+        index_collector_peers(c.id.as_str(), conn, limit).await?;
+    }
+    Ok(())
+}
+
+async fn connect(opts: &Opts, db_url: &str) -> Result<DbConnection, ScrapeError> {
+    #[cfg(not(feature="kafka"))]
+        let mut conn = DbConnection::new(db_url).await?;
+    #[cfg(feature="kafka")]
+        let mut conn = DbConnection::new_with_kafka(db_url, opts.kafka_broker.as_deref(), opts.kafka_topic.as_deref()).await?;
+    if let Some(url) = &opts.webhook_url {
+        let mut webhook = WebhookNotifier::new(url).with_batch_size(opts.webhook_batch_size);
+        if let Some(secret) = &opts.webhook_secret {
+            webhook = webhook.with_secret(secret);
+        }
+        conn = conn.with_notifier(Box::new(webhook));
+    }
+    Ok(conn)
+}
+
+/// Add the collectors registered by `discover --register` to the configured ones, only the one
+/// given by `--collector-id` if set.
+async fn with_registered(opts: &Opts, config: &Config, configured: &[Collector], conn: &DbConnection) -> Result<Vec<Collector>, ScrapeError> {
+    let mut collectors = configured.to_vec();
+    collectors.extend(registered_collectors(config, conn).await?.into_iter()
+        .filter(|c| opts.collector_id.as_ref().map(|id| id == &c.id).unwrap_or(true)));
+    Ok(collectors)
+}
+
+/// Drop inactive collectors and collectors of archival projects when crawling the latest months,
+/// and collectors not active in the crawled period in range mode.
+fn filter_retired(config: &Config, collectors: &[Collector], mode: CrawlMode) -> Vec<Collector> {
+    collectors.iter().filter(|c| {
+        // retired collectors and archival projects no longer change, so they are only indexed
+        // once in bootstrap mode, or re-indexed for a period they were active in
+        match mode.period() {
+            Some((from, to)) => c.active_range(from, to).is_some(),
+            None if mode == CrawlMode::Bootstrap => true,
+            None => c.meta.active && !config.is_archival(c.project.as_str())
+        }
+    }).cloned().collect()
+}
+
+/// Scrape all collectors, returning the IDs of the failed ones, and push the metrics if a
+/// Pushgateway is configured.
+async fn update(opts: &Opts, conn: &DbConnection, collectors: &[Collector], mode: CrawlMode, registry: &ScraperRegistry) -> Result<Vec<String>, ScrapeError> {
+    let buffer_size = match &mode {
+        CrawlMode::Latest| CrawlMode::TwoMonths => {20}
+        CrawlMode::Bootstrap | CrawlMode::Range {..} => {1}
+    };
+
+    let verify_limit = match opts.verify {
+        true => Some(opts.verify_limit),
+        false => None
+    };
+    let peers_limit = match opts.index_peers {
+        true => Some(opts.index_peers_limit),
+        false => None
+    };
+
+    let mut stream = futures::stream:: iter(collectors)
+        .map(|c| run_scraper(c, mode, registry, conn, verify_limit, peers_limit).map(move |res| (c, res)))
+        .buffer_unordered(buffer_size);
+
+    info!("start scraping for {} collectors", &collectors.len());
+    let mut failed = vec![];
+    while let Some((c, res)) = stream.next().await {
+        if let Err(e) = res {
+            error!("collector {} failed, skipped: {}", c.id, e);
+            metrics().collector_failures.with_label_values(&[c.id.as_str()]).inc();
+            failed.push(c.id.clone());
+        }
+    }
+
+    if let Some(gateway) = &opts.pushgateway {
+        if let Err(e) = metrics::push(gateway, opts.metrics_job.as_str()).await {
+            warn!("failed to push metrics: {}", e);
+        }
+    }
+    Ok(failed)
+}
+
+/// Forward each SIGTERM and SIGINT to the returned channel.
+fn shutdown_signals() -> UnboundedReceiver<()> {
+    let (tx, rx) = unbounded_channel();
+    #[cfg(unix)]
+    let mut terminate = signal(SignalKind::terminate()).expect("failed to install SIGTERM handler");
+    #[cfg(unix)]
+    let mut interrupt = signal(SignalKind::interrupt()).expect("failed to install SIGINT handler");
+    tokio::spawn(async move {
+        loop {
+            #[cfg(unix)]
+            tokio::select! {
+                _ = terminate.recv() => {}
+                _ = interrupt.recv() => {}
+            }
+            #[cfg(not(unix))]
+            let _ = tokio::signal::ctrl_c().await;
+            if tx.send(()).is_err() {
+                break
+            }
+        }
+    });
+    rx
+}
+
+/// Register the configured collectors and run scrapes of the active ones on schedule until
+/// shut down. Runs never overlap: a tick that comes due during a
+/// run is delayed until the run is done. On the first signal the current run is finished before
+/// exiting, a second signal interrupts it.
+async fn run_daemon(opts: &Opts, db_url: &str, config: &Config, configured: &[Collector], registry: &ScraperRegistry,
+                    latest_interval: Duration, sweep_interval: Duration) -> Result<(), ScrapeError> {
+    let conn = connect(opts, db_url).await?;
+    conn.insert_collectors(configured).await?;
+    metrics().init_latest(&conn.get_latest().await?);
+
+    let mut latest = tokio::time::interval(latest_interval);
+    latest.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut sweep = tokio::time::interval(sweep_interval);
+    sweep.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    let mut signals = shutdown_signals();
+
+    info!("running as daemon, latest files every {:?}, two months sweep every {:?}", latest_interval, sweep_interval);
+    loop {
+        let mode = tokio::select! {
+            biased;
+            _ = signals.recv() => break,
+            _ = sweep.tick() => CrawlMode::TwoMonths,
+            _ = latest.tick() => CrawlMode::Latest,
+        };
+
+        // reloaded for every run, so that collectors registered by `discover --register` while
+        // the daemon runs are scraped from the next run on
+        let collectors = match with_registered(opts, config, configured, &conn).await {
+            Ok(collectors) => filter_retired(config, &collectors, mode),
+            Err(e) => {
+                error!("{:?} run skipped, loading the registered collectors failed: {}", mode, e);
+                continue
+            }
+        };
+        let run = update(opts, &conn, collectors.as_slice(), mode, registry);
+        tokio::pin!(run);
+        let mut stopping = false;
+        let res = tokio::select! {
+            res = &mut run => res,
+            _ = signals.recv() => {
+                info!("shutting down after the current run, signal again to interrupt it");
+                stopping = true;
+                tokio::select! {
+                    res = &mut run => res,
+                    _ = signals.recv() => {
+                        info!("current run interrupted");
+                        break
+                    }
+                }
+            }
+        };
+        match res {
+            Ok(failed) if failed.is_empty() => info!("{:?} run finished", mode),
+            Ok(failed) => error!("{:?} run finished, {} of {} collectors failed: {}", mode, failed.len(), collectors.len(), failed.join(", ")),
+            Err(e) => error!("{:?} run failed: {}", mode, e),
+        }
+        if stopping {
+            break
+        }
+        if mode == CrawlMode::TwoMonths {
+            // the sweep covered the latest files as well
+            latest.reset();
+        }
+    }
+    info!("daemon stopped");
+    Ok(())
+}
+
+/// Run the updater with the command line options of the process and the given scrapers,
+/// exiting with status 1 on errors.
+pub fn main(registry: ScraperRegistry) {
+    // init logger
+    env_logger::init();
+
+    let _ = dotenv::dotenv();
+
+    let opts: Opts = Opts::parse();
+    if let Err(e) = run(&opts, registry) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn migrate(db_url: &str, action: &MigrateAction) -> Result<(), ScrapeError> {
+    let pool = connect_pool(db_url, 1).await?;
+    match action {
+        MigrateAction::Up => {
+            let applied = migrations::run_pending(&pool).await?;
+            info!("applied {} migrations", applied.len());
+        }
+        MigrateAction::Down => {
+            match migrations::revert_last(&pool).await? {
+                Some(m) => info!("reverted migration {}", m.name),
+                None => info!("no migration to revert"),
+            }
+        }
+        MigrateAction::Status => {
+            for (m, applied) in migrations::status(&pool).await? {
+                println!("[{}] {}", if applied { "X" } else { " " }, m.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Run the updater with the given options and scrapers. Collectors of projects without a scraper
+/// in the registry are rejected. A scraping run with failed collectors updates the other ones and
+/// exits with status 2.
+pub fn run(opts: &Opts, mut registry: ScraperRegistry) -> Result<(), ScrapeError> {
+    // configure async runtime
+    let blocking_cpus = match num_cpus::get() {
+        1 => 1,
+        n => n/2,
+    };
+
+    info!("using {} cores for parsing html pages", blocking_cpus);
+    let rt = tokio::runtime::Builder::new_multi_thread().enable_all()
+        .max_blocking_threads(blocking_cpus)
+        .build()?;
+
+    if let Some(Command::Migrate { action }) = &opts.command {
+        let db_url = get_db_url(opts.db_url.clone())?;
+        return rt.block_on(migrate(&db_url, action))
+    }
+
+    #[cfg(feature = "parquet")]
+    if let Some(Command::Export { output_dir, filter }) = &opts.command {
+        let filter = filter.to_filter();
+        let db_url = get_db_url(opts.db_url.clone())?;
+        let partitions = rt.block_on(async {
+            let conn = DbConnection::new(&db_url).await?;
+            export_items(&conn, &filter, std::path::Path::new(output_dir)).await
+        })?;
+        info!("exported {} items to {} partitions",
+              partitions.iter().map(|p| p.rows).sum::<usize>(), partitions.len());
+        return Ok(())
+    }
+
+    if let Some(Command::Dump { output, filter }) = &opts.command {
+        let filter = filter.to_filter();
+        let db_url = get_db_url(opts.db_url.clone())?;
+        let stats = rt.block_on(async {
+            let conn = DbConnection::new(&db_url).await?;
+            match output {
+                Some(path) => dump::dump(&conn, &filter, std::io::BufWriter::new(std::fs::File::create(path)?)).await,
+                None => dump::dump(&conn, &filter, std::io::BufWriter::new(std::io::stdout().lock())).await,
+            }
+        })?;
+        info!("dumped {} collectors and {} items", stats.collectors, stats.items);
+        return Ok(())
+    }
+
+    if let Some(Command::Load { input }) = &opts.command {
+        let db_url = get_db_url(opts.db_url.clone())?;
+        let stats = rt.block_on(async {
+            let conn = DbConnection::new(&db_url).await?;
+            match input {
+                Some(path) => dump::load(&conn, std::io::BufReader::new(std::fs::File::open(path)?)).await,
+                None => dump::load(&conn, std::io::stdin().lock()).await,
+            }
+        })?;
+        info!("loaded {} collectors and {} items, {} new", stats.collectors, stats.items, stats.new_items);
+        return Ok(())
+    }
+
+    let config_path = match &opts.collectors_config {
+        Some(path) => path,
+        None => Opts::command().error(
+            clap::ErrorKind::MissingRequiredArgument,
+            "--collectors-config is required unless running the migrate, export, dump or load subcommands"
+        ).exit()
+    };
+    let config = Config::from_file(config_path)?;
+    fetch::configure(config.fetch.clone())?;
+    let collectors = config.to_collectors().into_iter()
+        .filter(|c| {
+            match &opts.collector_id{
+                None => {true}
+                Some(id) => {
+                    id.as_str()==c.id
+                }
+            }
+        }).collect::<Vec<Collector>>();
+
+    config.register_local_scrapers(&mut registry)?;
+    for c in &collectors {
+        if !registry.contains(c.project.as_str()) {
+            return Err(ScrapeError::ConfigError(format!(
+                "no scraper registered for project {} of collector {}, available projects: {:?}",
+                c.project, c.id, registry.projects()
+            )))
+        }
+    }
+
+    match &opts.command {
+        Some(Command::Migrate { .. }) => unreachable!("migrations are run before loading the config"),
+        #[cfg(feature = "parquet")]
+        Some(Command::Export { .. }) => unreachable!("exports are run before loading the config"),
+        Some(Command::Dump { .. }) | Some(Command::Load { .. }) => unreachable!("dumps are run before loading the config"),
+        Some(Command::Gaps { from, to, format, output }) => {
+            let to: NaiveDateTime = match to {
+                Some(d) => d.and_hms_opt(0, 0, 0).unwrap(),
+                None => Utc::now().naive_utc(),
+            };
+            let from: NaiveDateTime = match from {
+                Some(d) => d.and_hms_opt(0, 0, 0).unwrap(),
+                None => to - chrono::Duration::days(7),
+            };
+
+            let db_url = get_db_url(opts.db_url.clone())?;
+            let gaps = rt.block_on(async {
+                let conn = connect(opts, &db_url).await?;
+                find_collector_gaps(&collectors, &registry, &conn, from, to).await
+            })?;
+            info!("found {} gaps for {} collectors", gaps.len(), collectors.len());
+
+            let content = match (format.as_str(), opts.pretty) {
+                ("csv", _) => gaps_to_csv(&gaps),
+                (_, true) => serde_json::to_string_pretty(&gaps).unwrap(),
+                (_, false) => serde_json::to_string(&gaps).unwrap(),
+            };
+            match output {
+                Some(path) => std::fs::write(path, content)?,
+                None => println!("{}", content),
+            }
+        }
+        Some(Command::Discover { register }) => {
+            let discoveries = rt.block_on(discover_collectors(&config, &registry))?;
+            for discovery in &discoveries {
+                for id in &discovery.stale {
+                    warn!("configured collector {} of {} is no longer found upstream", id, discovery.project);
+                }
+            }
+            if *register {
+                let db_url = get_db_url(opts.db_url.clone())?;
+                let registered = rt.block_on(async {
+                    let conn = DbConnection::new(&db_url).await?;
+                    register_new_collectors(&conn, &discoveries).await
+                })?;
+                info!("registered {} new collectors", registered.len());
+            }
+            let content = match opts.pretty {
+                true => serde_json::to_string_pretty(&discoveries).unwrap(),
+                false => serde_json::to_string(&discoveries).unwrap(),
+            };
+            println!("{}", content);
+        }
+        Some(Command::Daemon { latest_interval, sweep_interval }) => {
+            let db_url = get_db_url(opts.db_url.clone())?;
+            rt.block_on(run_daemon(
+                opts, &db_url, &config, &collectors, &registry,
+                Duration::from_secs(*latest_interval), Duration::from_secs(*sweep_interval)
+            ))?;
+        }
+        None => {
+            let mode = match &opts.mode {
+                Some(mode) => CrawlMode::new(mode.as_str(), opts.from.as_deref(), opts.to.as_deref())
+                    .map_err(ScrapeError::ParseError)?,
+                None => Opts::command().error(
+                    clap::ErrorKind::MissingRequiredArgument,
+                    "--mode is required unless running a subcommand"
+                ).exit()
+            };
+            let configured = collectors;
+
+            let db_url = get_db_url(opts.db_url.clone())?;
+            let (total, failed) = rt.block_on(async {
+                let conn = connect(opts, &db_url).await?;
+                conn.insert_collectors(&configured).await?;
+                let collectors = filter_retired(&config, &with_registered(opts, &config, &configured, &conn).await?, mode);
+                metrics().init_latest(&conn.get_latest().await?);
+                let failed = update(opts, &conn, &collectors, mode, &registry).await?;
+                Ok::<_, ScrapeError>((collectors.len(), failed))
+            })?;
+
+            if !failed.is_empty() {
+                // other collectors are updated, but exit with an error so that cron or the
+                // container supervisor reports the run
+                error!("{} of {} collectors failed: {}", failed.len(), total, failed.join(", "));
+                std::process::exit(2);
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_retired() {
+        let config: Config = serde_json::from_str(r#"{"projects": [
+            {"name": "riperis", "collectors": [
+                {"id": "rrc00", "url": "https://data.ris.ripe.net/rrc00"},
+                {"id": "rrc02", "url": "https://data.ris.ripe.net/rrc02", "active": false, "decommissioned_on": "2008-11-01"}
+            ]},
+            {"name": "isolario", "archival": true, "collectors": [
+                {"id": "Alderaan", "url": "https://www.isolario.it/Isolario_MRT_data/Alderaan"}
+            ]}
+        ]}"#).unwrap();
+        let collectors = config.to_collectors();
+        let ids = |mode: CrawlMode| filter_retired(&config, &collectors, mode).into_iter().map(|c| c.id).collect::<Vec<String>>();
+
+        assert_eq!(ids(CrawlMode::Latest), vec!["rrc00"]);
+        assert_eq!(ids(CrawlMode::TwoMonths), vec!["rrc00"]);
+        assert_eq!(ids(CrawlMode::Bootstrap), vec!["rrc00", "rrc02", "Alderaan"]);
+        // retired collectors and archival projects are re-indexed for periods they were active in
+        assert_eq!(ids(CrawlMode::new("range", Some("2008-01"), Some("2008-06")).unwrap()), vec!["rrc00", "rrc02", "Alderaan"]);
+        assert_eq!(ids(CrawlMode::new("range", Some("2019-01"), Some("2019-06")).unwrap()), vec!["rrc00", "Alderaan"]);
+    }
+}
@@ -0,0 +1,101 @@
+//! Verify availability and exact sizes of indexed files with HTTP HEAD requests.
+
+use futures::StreamExt;
+use log::info;
+use reqwest::header::CONTENT_LENGTH;
+use reqwest::{Method, StatusCode};
+use crate::db::DbConnection;
+use crate::db::models::Item;
+use crate::errors::ScrapeError;
+use crate::fetch::{fetcher, Fetcher};
+
+/// Number of concurrent HEAD requests per collector.
+const VERIFY_CONCURRENCY: usize = 10;
+
+/// Outcome of verifying one item.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum VerifyStatus {
+    /// the file exists, with the exact size in bytes from `Content-Length`
+    Size(i64),
+    /// the file returned 404
+    Missing,
+    /// the request failed or the response had no usable `Content-Length`, try again later
+    Unknown,
+}
+
+/// Issue a HEAD request to the URL and check its exact size.
+pub async fn verify_url(fetcher: &Fetcher, url: &str) -> VerifyStatus {
+    let resp = match fetcher.request(Method::HEAD, url).await {
+        Ok(resp) => resp,
+        Err(_) => return VerifyStatus::Unknown
+    };
+    if resp.status() == StatusCode::NOT_FOUND {
+        return VerifyStatus::Missing
+    }
+    if !resp.status().is_success() {
+        return VerifyStatus::Unknown
+    }
+    // `Response::content_length` reports the body size of the HEAD response, i.e. zero, so the
+    // header is read directly.
+    resp.headers().get(CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i64>().ok())
+        .map(VerifyStatus::Size)
+        .unwrap_or(VerifyStatus::Unknown)
+}
+
+/// Verify items concurrently, returning the status of each item in the same order.
+pub async fn verify_items(items: &[Item]) -> Vec<VerifyStatus> {
+    futures::stream::iter(items)
+        .map(|item| verify_url(fetcher(), item.url.as_str()))
+        .buffered(VERIFY_CONCURRENCY)
+        .collect().await
+}
+
+/// Verify up to `limit` most recent items of a collector that do not have exact sizes yet, and
+/// save the exact sizes and missing files to the database.
+pub async fn verify_collector(collector_id: &str, db: &DbConnection, limit: i64) -> Result<(), ScrapeError> {
+    let items = db.get_unverified_items(collector_id, limit).await?;
+    info!("verifying {} items for {}", items.len(), collector_id);
+    let statuses = verify_items(&items).await;
+
+    let mut sizes = vec![];
+    let mut missing = vec![];
+    for (item, status) in items.iter().zip(statuses) {
+        match status {
+            VerifyStatus::Size(size) => sizes.push((item.url.clone(), size)),
+            VerifyStatus::Missing => missing.push(item.url.clone()),
+            VerifyStatus::Unknown => {}
+        }
+    }
+    db.update_exact_sizes(&sizes).await?;
+    db.record_not_found(&missing).await?;
+    info!("verifying {} items for {}... {} verified, {} missing", items.len(), collector_id, sizes.len(), missing.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fetch::FetchConfig;
+    use crate::test_server::{serve, Response};
+
+    /// Serve HEAD requests: `/ok` with a `Content-Length` of 1234, `/error` with 500, anything
+    /// else with 404.
+    async fn start_server() -> String {
+        serve(|request| match (request.method.as_str(), request.path.as_str()) {
+            ("HEAD", "/ok") => Response::new("200 OK").header("Content-Length", "1234"),
+            ("HEAD", "/error") => Response::new("500 Internal Server Error"),
+            _ => Response::not_found(),
+        }).await
+    }
+
+    #[tokio::test]
+    async fn test_verify_url() {
+        let base = start_server().await;
+        let fetcher = Fetcher::new(FetchConfig { max_retries: 0, ..Default::default() });
+        assert_eq!(verify_url(&fetcher, format!("{}/ok", base).as_str()).await, VerifyStatus::Size(1234));
+        assert_eq!(verify_url(&fetcher, format!("{}/gone", base).as_str()).await, VerifyStatus::Missing);
+        assert_eq!(verify_url(&fetcher, format!("{}/error", base).as_str()).await, VerifyStatus::Unknown);
+    }
+}